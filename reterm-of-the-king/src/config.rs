@@ -0,0 +1,210 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::theme::{Theme, ThemeSpec};
+
+// User settings from `$XDG_CONFIG_HOME/reterm-of-the-king/config.toml`
+// (or `~/.config/...`). A missing file means defaults.
+//
+//     theme = "solarized-dark"
+//     on_exit = "close-on-success"
+//     scrollback_lines = 50000
+//
+//     [themes.mine]
+//     background = "#101010"
+//     foreground = "#c0c0c0"
+//
+//     [clipboard]
+//     osc52_read = "allow"
+//
+//     [links]
+//     editor = ["code", "--goto", "{file}:{line}:{col}"]
+//
+//     [shell]
+//     integration = false
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // name of the theme to start with, built-in or from `themes`
+    pub theme: Option<String>,
+    pub themes: BTreeMap<String, ThemeSpec>,
+    pub on_exit: OnExit,
+    // lines of history to keep; 0 keeps none
+    pub scrollback_lines: Option<usize>,
+    pub clipboard: ClipboardConfig,
+    pub links: LinksConfig,
+    pub shell: ShellConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShellConfig {
+    // start bash, zsh and fish with scripts that mark prompts and commands
+    // (OSC 133)
+    pub integration: bool,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self { integration: true }
+    }
+}
+
+// How links spotted in the output are opened.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    // command for `file:line:col` references, `{file}`, `{line}` and
+    // `{col}` filled in; without one the desktop opens the file
+    pub editor: Option<Vec<String>>,
+}
+
+// What applications may do with the clipboard through OSC 52.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClipboardConfig {
+    // let applications set the clipboard
+    pub osc52_write: bool,
+    // let applications read it; off by default, since anything running in
+    // the terminal (or on the far side of ssh) would see what you copied
+    pub osc52_read: Osc52Read,
+    // largest text either way, in bytes
+    pub osc52_max_bytes: usize,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            osc52_write: true,
+            osc52_read: Osc52Read::Deny,
+            osc52_max_bytes: 1 << 20,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Osc52Read {
+    #[default]
+    Deny,
+    Allow,
+}
+
+// What to do with the window once the shell has exited.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnExit {
+    // keep the window and its contents, showing the exit status
+    #[default]
+    Hold,
+    Close,
+    // close if the shell exited with status 0, hold otherwise
+    CloseOnSuccess,
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("reterm-of-the-king"))
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = config_dir().map(|d| d.join("config.toml")) else {
+            return Self::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return Self::default(),
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    // Built-in themes followed by the user's, which replace built-ins of the
+    // same name.
+    pub fn themes(&self) -> Vec<Theme> {
+        let mut themes = Theme::builtin();
+        for (name, spec) in &self.themes {
+            match Theme::from_spec(name, spec) {
+                Ok(theme) => match themes.iter_mut().find(|t| t.name == *name) {
+                    Some(slot) => *slot = theme,
+                    None => themes.push(theme),
+                },
+                Err(e) => eprintln!("config: {e}"),
+            }
+        }
+        themes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn user_themes_replace_builtins_and_add_to_them() {
+        let config = parse(
+            r##"
+            theme = "mine"
+
+            [themes.solarized-dark]
+            background = "#000001"
+
+            [themes.mine]
+            foreground = "#c0c0c0"
+
+            [themes.broken]
+            ansi = ["#000000"]
+            "##,
+        );
+        let themes = config.themes();
+        let names: Vec<&str> = themes.iter().map(|t| t.name.as_str()).collect();
+        // the broken theme is reported and left out
+        assert_eq!(names, ["xterm", "solarized-dark", "solarized-light", "gruvbox-dark", "mine"]);
+        let solarized = &themes[1];
+        assert_eq!(solarized.background, eframe::egui::Color32::from_rgb(0, 0, 1));
+        // a replaced built-in starts from the xterm theme, not from itself
+        assert_eq!(solarized.foreground, Theme::default().foreground);
+        assert_eq!(config.theme.as_deref(), Some("mine"));
+    }
+
+    #[test]
+    fn settings_and_defaults() {
+        let config = parse("");
+        assert_eq!(config.themes().len(), Theme::builtin().len());
+        assert!(config.on_exit == OnExit::Hold && config.scrollback_lines.is_none());
+        assert!(config.clipboard.osc52_write && config.clipboard.osc52_read == Osc52Read::Deny);
+        assert!(config.shell.integration && config.links.editor.is_none());
+
+        let config = parse(
+            r#"
+            on_exit = "close-on-success"
+            scrollback_lines = 500
+
+            [clipboard]
+            osc52_read = "allow"
+
+            [links]
+            editor = ["vim", "+{line}", "{file}"]
+            "#,
+        );
+        assert!(config.on_exit == OnExit::CloseOnSuccess);
+        assert_eq!(config.scrollback_lines, Some(500));
+        assert!(config.clipboard.osc52_read == Osc52Read::Allow);
+        assert_eq!(config.links.editor.unwrap(), ["vim", "+{line}", "{file}"]);
+
+        // typos are errors rather than silently ignored
+        assert!(toml::from_str::<Config>("scrollback = 5").is_err());
+        assert!(toml::from_str::<Config>("[themes.x]\nbackgound = \"#000000\"").is_err());
+    }
+}
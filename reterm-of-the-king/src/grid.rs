@@ -0,0 +1,932 @@
+// Screen model: a rows×cols grid of cells plus a cursor. Knows nothing about
+// egui or escape sequences; `Term` drives it and the app paints it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::scrollback::Scrollback;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Color {
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Flags(u16);
+
+impl Flags {
+    pub const BOLD: Flags = Flags(1 << 0);
+    pub const DIM: Flags = Flags(1 << 1);
+    pub const ITALIC: Flags = Flags(1 << 2);
+    pub const BLINK: Flags = Flags(1 << 3);
+    pub const INVERSE: Flags = Flags(1 << 4);
+    pub const HIDDEN: Flags = Flags(1 << 5);
+    pub const STRIKE: Flags = Flags(1 << 6);
+    // left half of a double-width character
+    pub const WIDE: Flags = Flags(1 << 7);
+    // right half, covered by the character to its left; also the last
+    // column left empty where a wide character wrapped early
+    pub const SPACER: Flags = Flags(1 << 8);
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+}
+
+// SGR 4:x underline styles; kept apart from `Flags` since they are
+// mutually exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    // zero-width characters (combining marks) printed after `c`, '\0' when
+    // unused; xterm keeps two as well
+    pub combining: [char; 2],
+    pub fg: Color,
+    pub bg: Color,
+    pub flags: Flags,
+    pub underline: Underline,
+    // SGR 58; `Color::Default` means "same as the foreground"
+    pub underline_color: Color,
+    // OSC 8 hyperlink, as numbered by `Term::hyperlink`; 0 for none
+    pub link: u32,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            c: ' ',
+            combining: ['\0'; 2],
+            fg: Color::Default,
+            bg: Color::Default,
+            flags: Flags::default(),
+            underline: Underline::None,
+            underline_color: Color::Default,
+            link: 0,
+        }
+    }
+}
+
+impl Cell {
+    // Everything but the character matches, so both can be drawn in one run.
+    pub fn same_style(&self, other: &Cell) -> bool {
+        let style = |cell: &Cell| Cell { c: ' ', combining: ['\0'; 2], ..*cell };
+        style(self) == style(other)
+    }
+
+    // What the cell shows as text: its character and combining marks, or
+    // nothing for the right half of a wide character.
+    pub fn text(&self) -> impl Iterator<Item = char> {
+        let c = (!self.flags.contains(Flags::SPACER)).then_some(self.c);
+        c.into_iter().chain(self.combining.into_iter().filter(|&m| m != '\0'))
+    }
+
+    // A blank that keeps the background of `template`, as erase operations do.
+    pub fn blank(template: &Cell) -> Self {
+        Self {
+            bg: template.bg,
+            ..Self::default()
+        }
+    }
+}
+
+// Where a shell says its prompt, command line, output and exit status begin
+// (OSC 133 A/B/C/D).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkKind {
+    Prompt,
+    Input,
+    Output,
+    // the command finished, with its exit status if the shell gave one
+    Finished(Option<i32>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mark {
+    pub kind: MarkKind,
+    pub col: usize,
+}
+
+// Whether a row ends in the padding left where a wide character wrapped
+// early: a spacer with no wide character before it.
+fn is_padding(cells: &[Cell]) -> bool {
+    match cells {
+        [.., before, last] => last.flags.contains(Flags::SPACER) && !before.flags.contains(Flags::WIDE),
+        [last] => last.flags.contains(Flags::SPACER),
+        [] => false,
+    }
+}
+
+// Row versions come from one process-wide counter, so a version names one
+// exact row content no matter which grid (or scrollback) the row ends up in.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub cells: Vec<Cell>,
+    // The line continues on the next row (soft wrap), as opposed to ending
+    // in a hard newline. Lets resize re-join and re-wrap it.
+    pub wrapped: bool,
+    // shell integration marks on this row, in the order they arrived
+    pub marks: Vec<Mark>,
+    // Damage tracking: bumped by every change to the row, so the renderer
+    // can reuse its layout for rows whose version it has already seen.
+    version: u64,
+}
+
+impl Row {
+    pub fn new(cols: usize, template: &Cell) -> Self {
+        Self::from_cells(vec![Cell::blank(template); cols], false)
+    }
+
+    fn from_cells(cells: Vec<Cell>, wrapped: bool) -> Self {
+        Self {
+            cells,
+            wrapped,
+            marks: Vec::new(),
+            version: next_version(),
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn is_blank(&self) -> bool {
+        !self.wrapped && self.cells.iter().all(|c| *c == Cell::default())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Cursor {
+    pub row: usize,
+    pub col: usize,
+}
+
+pub struct Grid {
+    rows: Vec<Row>,
+    cols: usize,
+    pub cursor: Cursor,
+    // Set after printing into the last column; the next print wraps first.
+    wrap_pending: bool,
+    // Inclusive top/bottom rows of the scrolling region (DECSTBM).
+    scroll_top: usize,
+    scroll_bottom: usize,
+    pub autowrap: bool,
+    pub scrollback: Scrollback,
+}
+
+impl Grid {
+    pub fn new(rows: usize, cols: usize, scrollback_lines: usize) -> Self {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        Self {
+            rows: vec![Row::new(cols, &Cell::default()); rows],
+            cols,
+            cursor: Cursor::default(),
+            wrap_pending: false,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            scrollback: Scrollback::new(scrollback_lines),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    // Resize to rows×cols. With `reflow`, a width change re-wraps soft-wrapped
+    // lines first. Shrinking pushes lines above the cursor into the
+    // scrollback so the cursor stays on screen; growing pulls them back.
+    pub fn resize(&mut self, rows: usize, cols: usize, reflow: bool) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        let blank = Cell::default();
+
+        if reflow && cols != self.cols {
+            self.reflow(cols);
+        }
+
+        if rows < self.rows.len() {
+            let shift = (self.cursor.row + 1).saturating_sub(rows);
+            for row in self.rows.drain(..shift) {
+                self.scrollback.push(row);
+            }
+            self.rows.truncate(rows);
+            self.cursor.row -= shift;
+        }
+        while self.rows.len() < rows {
+            match self.scrollback.pop() {
+                Some(row) => {
+                    self.rows.insert(0, row);
+                    self.cursor.row += 1;
+                }
+                None => self.rows.push(Row::new(cols, &blank)),
+            }
+        }
+        for row in &mut self.rows {
+            if row.cells.len() != cols {
+                row.cells.resize(cols, blank);
+                row.version = next_version();
+            }
+        }
+
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.move_to(self.cursor.row, self.cursor.col);
+    }
+
+    // Re-join soft-wrapped rows of screen and scrollback into logical lines
+    // and wrap them again at `cols`, keeping the cursor on the same character.
+    fn reflow(&mut self, cols: usize) {
+        let blank = Cell::default();
+        let screen_rows = self.rows.len();
+        let history = self.scrollback.take();
+        let cursor_row = history.len() + self.cursor.row;
+
+        // (logical line, offset into it) of the cursor
+        let mut cursor_at = (0, 0);
+        // each line's marks, with columns as offsets into the line
+        let mut lines: Vec<(Vec<Cell>, Vec<Mark>)> = Vec::new();
+        let mut current = Vec::new();
+        let mut marks = Vec::new();
+        for (i, row) in history.into_iter().chain(self.rows.drain(..)).enumerate() {
+            if i == cursor_row {
+                // a pending wrap means the next character goes after the
+                // last column, not over it
+                let col = self.cursor.col + self.wrap_pending as usize;
+                cursor_at = (lines.len(), current.len() + col);
+            }
+            let offset = current.len();
+            marks.extend(row.marks.into_iter().map(|m| Mark { col: offset + m.col, ..m }));
+            let mut cells = row.cells;
+            // padding where a wide character didn't fit; it wraps anew
+            if row.wrapped && is_padding(&cells) {
+                cells.pop();
+            }
+            current.extend(cells);
+            if !row.wrapped {
+                lines.push((std::mem::take(&mut current), std::mem::take(&mut marks)));
+            }
+        }
+        if !current.is_empty() {
+            lines.push((current, marks));
+        }
+
+        let mut rows: Vec<Row> = Vec::new();
+        let mut cursor = Cursor::default();
+        for (i, (mut line, marks)) in lines.into_iter().enumerate() {
+            let first_row = rows.len();
+            while line.last() == Some(&blank) {
+                line.pop();
+            }
+            if i == cursor_at.0 && line.len() <= cursor_at.1 {
+                line.resize(cursor_at.1 + 1, blank);
+            }
+            // where each row of the line starts in it; a wide character that
+            // would straddle the edge moves down whole
+            let mut starts = vec![0];
+            while line.len() - starts[starts.len() - 1] > cols {
+                let mut end = starts[starts.len() - 1] + cols;
+                if cols > 1 && line[end - 1].flags.contains(Flags::WIDE) {
+                    end -= 1;
+                }
+                starts.push(end);
+            }
+            // (row, col) of an offset into the line; marks past the trimmed
+            // text stay on its last row
+            let locate = |at: usize| {
+                let n = starts.partition_point(|&start| start <= at) - 1;
+                (first_row + n, (at - starts[n]).min(cols - 1))
+            };
+            if i == cursor_at.0 {
+                let (row, col) = locate(cursor_at.1);
+                cursor = Cursor { row, col };
+            }
+            for (n, &start) in starts.iter().enumerate() {
+                let end = starts.get(n + 1).copied().unwrap_or(line.len());
+                let mut cells = line[start..end].to_vec();
+                cells.resize(cols, blank);
+                if end - start < cols && n + 1 < starts.len() {
+                    cells[cols - 1].flags.insert(Flags::SPACER);
+                }
+                rows.push(Row::from_cells(cells, n + 1 < starts.len()));
+            }
+            for mark in marks {
+                let (row, col) = locate(mark.col);
+                rows[row].marks.push(Mark { col, ..mark });
+            }
+        }
+
+        // Blank rows below the cursor are just unused screen, not history.
+        while rows.len() > cursor.row + 1 && rows.last().is_some_and(Row::is_blank) {
+            rows.pop();
+        }
+
+        let start = rows.len().saturating_sub(screen_rows).min(cursor.row);
+        for row in rows.drain(..start) {
+            self.scrollback.push(row);
+        }
+        while rows.len() < screen_rows {
+            rows.push(Row::new(cols, &blank));
+        }
+        cursor.row -= start;
+
+        self.rows = rows;
+        self.cols = cols;
+        self.cursor = cursor;
+        self.wrap_pending = false;
+    }
+
+    // Mutable access to a screen row, marking it damaged.
+    fn row_mut(&mut self, row: usize) -> &mut Row {
+        let row = &mut self.rows[row];
+        row.version = next_version();
+        row
+    }
+
+    // Row `row` of the viewport when scrolled back `offset` lines into history.
+    pub fn visible_row(&self, offset: usize, row: usize) -> &Row {
+        let history = self.scrollback.len();
+        let offset = offset.min(history);
+        let line = history - offset + row;
+        match line.checked_sub(history) {
+            Some(r) => &self.rows[r],
+            None => self.scrollback.get(line).expect("line within scrollback"),
+        }
+    }
+
+    // Absolute number of viewport row `row` when scrolled back `offset`
+    // lines. A line keeps its number as it scrolls into history, so positions
+    // stored this way stay on the same text while output arrives.
+    pub fn line_number(&self, offset: usize, row: usize) -> usize {
+        self.scrollback.total() - offset.min(self.scrollback.len()) + row
+    }
+
+    // The row with absolute number `line`, unless it fell out of history.
+    pub fn line(&self, line: usize) -> Option<&Row> {
+        let history = self.scrollback.len();
+        let i = line.checked_sub(self.scrollback.total() - history)?;
+        match i.checked_sub(history) {
+            Some(r) => self.rows.get(r),
+            None => self.scrollback.get(i),
+        }
+    }
+
+    // Mark the cursor position for shell integration. A prompt redrawn in
+    // place marks its row again, replacing the earlier mark of that kind.
+    pub fn mark(&mut self, kind: MarkKind) {
+        let col = self.cursor.col;
+        let marks = &mut self.rows[self.cursor.row].marks;
+        marks.retain(|m| std::mem::discriminant(&m.kind) != std::mem::discriminant(&kind));
+        marks.push(Mark { kind, col });
+    }
+
+    // History then screen, oldest row first.
+    pub fn all_rows(&self) -> impl Iterator<Item = &Row> {
+        let history = (0..self.scrollback.len()).filter_map(|i| self.scrollback.get(i));
+        history.chain(&self.rows)
+    }
+
+    // Every mark in history and on screen as (absolute line, mark), oldest
+    // first.
+    pub fn marks(&self) -> impl Iterator<Item = (usize, Mark)> + '_ {
+        let first = self.scrollback.total() - self.scrollback.len();
+        self.all_rows()
+            .enumerate()
+            .flat_map(move |(i, row)| row.marks.iter().map(move |m| (first + i, *m)))
+    }
+
+    // Write `c` at the cursor with the attributes of `template`, wrapping and
+    // scrolling as needed. Wide characters take two cells, the second a
+    // spacer; zero-width ones join the character before the cursor.
+    pub fn put_char(&mut self, c: char, template: &Cell) {
+        let width = c.width().unwrap_or(1);
+        if width == 0 {
+            self.combine(c);
+            return;
+        }
+        // a wide character doesn't fit in the last column: it wraps early,
+        // or without autowrap isn't printed
+        let no_room = width == 2 && self.cursor.col + 1 >= self.cols;
+        if width > self.cols || (no_room && !self.autowrap) {
+            return;
+        }
+        if no_room && !self.wrap_pending {
+            let last = self.cols - 1;
+            let last = &mut self.row_mut(self.cursor.row).cells[last];
+            *last = Cell::blank(template);
+            last.flags.insert(Flags::SPACER);
+        }
+        if self.wrap_pending || no_room {
+            self.wrap_pending = false;
+            self.row_mut(self.cursor.row).wrapped = true;
+            self.carriage_return();
+            self.linefeed(template);
+        }
+        let Cursor { row, col } = self.cursor;
+        self.split_wide(row, col);
+        let mut cell = Cell { c, ..*template };
+        if width == 2 {
+            self.split_wide(row, col + 1);
+            cell.flags.insert(Flags::WIDE);
+            let mut spacer = *template;
+            spacer.flags.insert(Flags::SPACER);
+            self.row_mut(row).cells[col + 1] = spacer;
+        }
+        self.row_mut(row).cells[col] = cell;
+        if col + width < self.cols {
+            self.cursor.col += width;
+        } else {
+            self.cursor.col = self.cols - 1;
+            self.wrap_pending = self.autowrap;
+        }
+    }
+
+    // Before writing over one half of a wide character, blank the other.
+    fn split_wide(&mut self, row: usize, col: usize) {
+        let cells = &mut self.row_mut(row).cells;
+        let flags = cells[col].flags;
+        if flags.contains(Flags::SPACER) && col > 0 {
+            cells[col - 1] = Cell::blank(&cells[col - 1]);
+        }
+        if flags.contains(Flags::WIDE) && col + 1 < cells.len() {
+            cells[col + 1] = Cell::blank(&cells[col + 1]);
+        }
+    }
+
+    // Attach a zero-width character to the last one printed. Marks beyond
+    // what a cell holds are dropped.
+    fn combine(&mut self, c: char) {
+        let Cursor { row, mut col } = self.cursor;
+        if !self.wrap_pending {
+            let Some(prev) = col.checked_sub(1) else {
+                return;
+            };
+            col = prev;
+        }
+        let cells = &mut self.row_mut(row).cells;
+        if cells[col].flags.contains(Flags::SPACER) && col > 0 {
+            col -= 1;
+        }
+        if let Some(slot) = cells[col].combining.iter_mut().find(|m| **m == '\0') {
+            *slot = c;
+        }
+    }
+
+    pub fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor.row = row.min(self.rows() - 1);
+        self.cursor.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    // Relative vertical moves stop at the scroll region margins when the
+    // cursor starts inside it, like xterm's CUU/CUD.
+    pub fn move_up(&mut self, n: usize) {
+        let limit = if self.cursor.row >= self.scroll_top { self.scroll_top } else { 0 };
+        let row = self.cursor.row.saturating_sub(n).max(limit);
+        self.move_to(row, self.cursor.col);
+    }
+
+    pub fn move_down(&mut self, n: usize) {
+        let limit = if self.cursor.row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            self.rows() - 1
+        };
+        let row = (self.cursor.row + n).min(limit);
+        self.move_to(row, self.cursor.col);
+    }
+
+    pub fn move_left(&mut self, n: usize) {
+        self.move_to(self.cursor.row, self.cursor.col.saturating_sub(n));
+    }
+
+    pub fn move_right(&mut self, n: usize) {
+        self.move_to(self.cursor.row, self.cursor.col + n);
+    }
+
+    pub fn carriage_return(&mut self) {
+        self.cursor.col = 0;
+        self.wrap_pending = false;
+    }
+
+    pub fn backspace(&mut self) {
+        self.move_left(1);
+    }
+
+    pub fn tab(&mut self) {
+        let next = (self.cursor.col / 8 + 1) * 8;
+        self.move_to(self.cursor.row, next);
+    }
+
+    pub fn linefeed(&mut self, template: &Cell) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1, template);
+        } else if self.cursor.row + 1 < self.rows() {
+            self.cursor.row += 1;
+        }
+    }
+
+    pub fn reverse_index(&mut self, template: &Cell) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1, template);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.rows() - 1);
+        if top < bottom {
+            self.scroll_top = top;
+            self.scroll_bottom = bottom;
+        } else {
+            self.scroll_top = 0;
+            self.scroll_bottom = self.rows() - 1;
+        }
+        self.move_to(0, 0);
+    }
+
+    // Scroll the region up by `n`, blanking rows at the bottom. Rows leaving
+    // the top of the screen go to the scrollback.
+    pub fn scroll_up(&mut self, n: usize, template: &Cell) {
+        if self.scroll_top == 0 {
+            let n = n.min(self.scroll_bottom + 1);
+            for row in &mut self.rows[..n] {
+                let blank = Row::new(self.cols, template);
+                self.scrollback.push(std::mem::replace(row, blank));
+            }
+        }
+        self.shift_up(self.scroll_top, n, template);
+    }
+
+    // Scroll the region down by `n`, blanking rows at the top.
+    pub fn scroll_down(&mut self, n: usize, template: &Cell) {
+        self.shift_down(self.scroll_top, n, template);
+    }
+
+    // Move rows `top..=scroll_bottom` up by `n`; rows leaving at `top` are
+    // dropped and blanks come in at the bottom.
+    fn shift_up(&mut self, top: usize, n: usize, template: &Cell) {
+        let bottom = self.scroll_bottom;
+        let n = n.min(bottom - top + 1);
+        self.rows[top..=bottom].rotate_left(n);
+        for row in &mut self.rows[bottom + 1 - n..=bottom] {
+            *row = Row::new(self.cols, template);
+        }
+    }
+
+    fn shift_down(&mut self, top: usize, n: usize, template: &Cell) {
+        let bottom = self.scroll_bottom;
+        let n = n.min(bottom - top + 1);
+        self.rows[top..=bottom].rotate_right(n);
+        for row in &mut self.rows[top..top + n] {
+            *row = Row::new(self.cols, template);
+        }
+    }
+
+    // IL/DL: only act when the cursor is inside the scroll region. Deleted
+    // lines are gone, not scrolled into history.
+    pub fn insert_lines(&mut self, n: usize, template: &Cell) {
+        let row = self.cursor.row;
+        if row < self.scroll_top || row > self.scroll_bottom {
+            return;
+        }
+        self.shift_down(row, n, template);
+        self.carriage_return();
+    }
+
+    pub fn delete_lines(&mut self, n: usize, template: &Cell) {
+        let row = self.cursor.row;
+        if row < self.scroll_top || row > self.scroll_bottom {
+            return;
+        }
+        self.shift_up(row, n, template);
+        self.carriage_return();
+    }
+
+    pub fn insert_chars(&mut self, n: usize, template: &Cell) {
+        let Cursor { row, col } = self.cursor;
+        let cells = &mut self.row_mut(row).cells[col..];
+        let n = n.min(cells.len());
+        cells.rotate_right(n);
+        cells[..n].fill(Cell::blank(template));
+        self.wrap_pending = false;
+    }
+
+    pub fn delete_chars(&mut self, n: usize, template: &Cell) {
+        let Cursor { row, col } = self.cursor;
+        let cells = &mut self.row_mut(row).cells[col..];
+        let n = n.min(cells.len());
+        cells.rotate_left(n);
+        let len = cells.len();
+        cells[len - n..].fill(Cell::blank(template));
+        self.wrap_pending = false;
+    }
+
+    pub fn erase_chars(&mut self, n: usize, template: &Cell) {
+        let Cursor { row, col } = self.cursor;
+        let end = (col + n).min(self.cols);
+        self.row_mut(row).cells[col..end].fill(Cell::blank(template));
+        self.wrap_pending = false;
+    }
+
+    pub fn clear_line_right(&mut self, template: &Cell) {
+        let Cursor { row, col } = self.cursor;
+        self.row_mut(row).cells[col..].fill(Cell::blank(template));
+        self.wrap_pending = false;
+    }
+
+    pub fn clear_line_left(&mut self, template: &Cell) {
+        let Cursor { row, col } = self.cursor;
+        self.row_mut(row).cells[..=col].fill(Cell::blank(template));
+    }
+
+    pub fn clear_line(&mut self, template: &Cell) {
+        let row = self.cursor.row;
+        self.rows[row] = Row::new(self.cols, template);
+    }
+
+    pub fn clear_below(&mut self, template: &Cell) {
+        self.clear_line_right(template);
+        for row in &mut self.rows[self.cursor.row + 1..] {
+            *row = Row::new(self.cols, template);
+        }
+    }
+
+    pub fn clear_above(&mut self, template: &Cell) {
+        self.clear_line_left(template);
+        for row in &mut self.rows[..self.cursor.row] {
+            *row = Row::new(self.cols, template);
+        }
+    }
+
+    pub fn clear(&mut self, template: &Cell) {
+        for row in &mut self.rows {
+            *row = Row::new(self.cols, template);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(row: &Row) -> String {
+        row.cells.iter().flat_map(Cell::text).collect::<String>().trim_end().to_owned()
+    }
+
+    fn screen(grid: &Grid) -> Vec<String> {
+        (0..grid.rows()).map(|r| text(grid.visible_row(0, r))).collect()
+    }
+
+    fn history(grid: &Grid) -> Vec<String> {
+        (0..grid.scrollback.len()).map(|i| text(grid.scrollback.get(i).unwrap())).collect()
+    }
+
+    // Print `s`, with '\n' as CR LF.
+    fn print(grid: &mut Grid, s: &str) {
+        let t = Cell::default();
+        for c in s.chars() {
+            match c {
+                '\n' => {
+                    grid.carriage_return();
+                    grid.linefeed(&t);
+                }
+                '\t' => grid.tab(),
+                c => grid.put_char(c, &t),
+            }
+        }
+    }
+
+    #[test]
+    fn delete_lines_at_the_top_drops_them() {
+        let t = Cell::default();
+        let mut grid = Grid::new(3, 10, 100);
+        print(&mut grid, "one\ntwo\nthree");
+        grid.move_to(0, 0);
+        grid.delete_lines(1, &t);
+        assert_eq!(screen(&grid), ["two", "three", ""]);
+        assert!(history(&grid).is_empty());
+
+        grid.move_to(0, 2);
+        grid.insert_lines(1, &t);
+        assert_eq!(screen(&grid), ["", "two", "three"]);
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 0 });
+        assert!(history(&grid).is_empty());
+
+        // a real scroll still keeps what leaves the top
+        grid.scroll_up(1, &t);
+        assert_eq!(history(&grid), [""]);
+        grid.move_to(2, 0);
+        print(&mut grid, "four\nfive");
+        assert_eq!(history(&grid), ["", "two"]);
+        assert_eq!(screen(&grid), ["three", "four", "five"]);
+    }
+
+    #[test]
+    fn reflow_widening_joins_wrapped_rows() {
+        let mut grid = Grid::new(3, 5, 100);
+        // the hard newline after "fg" stays one
+        print(&mut grid, "abcdefg\nhi");
+        assert_eq!(screen(&grid), ["abcde", "fg", "hi"]);
+        grid.resize(3, 10, true);
+        assert_eq!(screen(&grid), ["abcdefg", "hi", ""]);
+        assert_eq!(grid.cursor, Cursor { row: 1, col: 2 });
+        assert!(!grid.visible_row(0, 0).wrapped);
+    }
+
+    #[test]
+    fn reflow_narrowing_wraps_into_history() {
+        let mut grid = Grid::new(2, 10, 100);
+        print(&mut grid, "abcdefgh\nxy");
+        grid.resize(2, 4, true);
+        assert_eq!(history(&grid), ["abcd"]);
+        assert_eq!(screen(&grid), ["efgh", "xy"]);
+        assert!(grid.scrollback.get(0).unwrap().wrapped);
+        assert!(!grid.visible_row(0, 0).wrapped);
+        assert_eq!(grid.cursor, Cursor { row: 1, col: 2 });
+    }
+
+    #[test]
+    fn reflow_keeps_the_cursor_on_its_character() {
+        let mut grid = Grid::new(3, 5, 100);
+        print(&mut grid, "abcdefgh");
+        // on the 'g'
+        grid.move_to(1, 1);
+        grid.resize(3, 10, true);
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 6 });
+        grid.resize(3, 4, true);
+        assert_eq!(grid.cursor, Cursor { row: 1, col: 2 });
+        assert_eq!(grid.visible_row(0, 1).cells[2].c, 'g');
+    }
+
+    #[test]
+    fn reflow_keeps_a_pending_wrap() {
+        let mut grid = Grid::new(3, 5, 100);
+        print(&mut grid, "abcde");
+        grid.resize(3, 10, true);
+        print(&mut grid, "X");
+        assert_eq!(screen(&grid), ["abcdeX", "", ""]);
+    }
+
+    #[test]
+    fn put_char_and_cursor_moves() {
+        let mut grid = Grid::new(3, 10, 0);
+        print(&mut grid, "ab\tc");
+        assert_eq!(screen(&grid), ["ab      c", "", ""]);
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 9 });
+        grid.move_to(7, 20);
+        assert_eq!(grid.cursor, Cursor { row: 2, col: 9 });
+        grid.move_left(3);
+        grid.move_up(1);
+        assert_eq!(grid.cursor, Cursor { row: 1, col: 6 });
+        grid.backspace();
+        print(&mut grid, "x");
+        assert_eq!(screen(&grid)[1], "     x");
+    }
+
+    #[test]
+    fn autowrap_waits_for_the_next_char() {
+        let mut grid = Grid::new(2, 3, 0);
+        print(&mut grid, "abc");
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 2 });
+        print(&mut grid, "d");
+        assert_eq!(screen(&grid), ["abc", "d"]);
+        assert!(grid.visible_row(0, 0).wrapped);
+
+        let mut grid = Grid::new(2, 3, 0);
+        grid.autowrap = false;
+        print(&mut grid, "abcd");
+        assert_eq!(screen(&grid), ["abd", ""]);
+    }
+
+    #[test]
+    fn scroll_region_keeps_the_rest_in_place() {
+        let t = Cell::default();
+        let mut grid = Grid::new(4, 10, 100);
+        print(&mut grid, "one\ntwo\nthree\nfour");
+        grid.set_scroll_region(1, 2);
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 0 });
+        grid.move_to(2, 0);
+        grid.linefeed(&t);
+        assert_eq!(screen(&grid), ["one", "three", "", "four"]);
+        // only scrolling from the top of the screen feeds history
+        assert!(history(&grid).is_empty());
+        grid.move_to(1, 0);
+        grid.reverse_index(&t);
+        grid.reverse_index(&t);
+        assert_eq!(screen(&grid), ["one", "", "", "four"]);
+        // moves inside the region stop at its margins
+        grid.move_to(2, 0);
+        grid.move_down(5);
+        assert_eq!(grid.cursor.row, 2);
+    }
+
+    #[test]
+    fn clears() {
+        let t = Cell::default();
+        let mut grid = Grid::new(3, 5, 0);
+        print(&mut grid, "abcde\nfghij\nklmno");
+        grid.move_to(1, 2);
+        grid.clear_line_right(&t);
+        assert_eq!(screen(&grid), ["abcde", "fg", "klmno"]);
+        grid.clear_line_left(&t);
+        assert_eq!(screen(&grid), ["abcde", "", "klmno"]);
+        grid.move_to(0, 1);
+        grid.erase_chars(2, &t);
+        grid.clear_below(&t);
+        assert_eq!(screen(&grid), ["a", "", ""]);
+        // erases keep the background of the current attributes
+        let red = Cell { bg: Color::Indexed(1), ..t };
+        grid.clear(&red);
+        assert!(grid.visible_row(0, 2).cells.iter().all(|c| c.bg == red.bg && c.c == ' '));
+    }
+
+    #[test]
+    fn wide_chars_take_two_cells() {
+        let mut grid = Grid::new(3, 5, 0);
+        print(&mut grid, "a世b");
+        let cells = &grid.visible_row(0, 0).cells;
+        assert!(cells[1].flags.contains(Flags::WIDE) && cells[2].flags.contains(Flags::SPACER));
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 4 });
+        // no room in the last column: it wraps, leaving padding
+        print(&mut grid, "界");
+        assert_eq!(screen(&grid), ["a世b", "界", ""]);
+        assert!(grid.visible_row(0, 0).wrapped);
+        assert_eq!(grid.cursor, Cursor { row: 1, col: 2 });
+
+        // writing over half of one blanks the other half
+        grid.move_to(0, 2);
+        print(&mut grid, "x");
+        assert_eq!(screen(&grid)[0], "a xb");
+        grid.move_to(1, 0);
+        print(&mut grid, "y");
+        assert_eq!(screen(&grid)[1], "y");
+        assert_eq!(grid.visible_row(0, 1).cells[1], Cell::default());
+    }
+
+    #[test]
+    fn combining_marks_join_the_previous_char() {
+        let mut grid = Grid::new(2, 3, 0);
+        print(&mut grid, "e\u{301}世\u{302}\u{303}\u{304}");
+        assert_eq!(screen(&grid)[0], "e\u{301}世\u{302}\u{303}");
+        assert_eq!(grid.cursor, Cursor { row: 0, col: 2 });
+        // after the last column, with the wrap still pending
+        let mut grid = Grid::new(2, 2, 0);
+        print(&mut grid, "ab\u{301}");
+        assert_eq!(screen(&grid), ["ab\u{301}", ""]);
+        // nothing before it to join
+        print(&mut grid, "\n\u{301}");
+        assert_eq!(screen(&grid), ["ab\u{301}", ""]);
+    }
+
+    #[test]
+    fn reflow_moves_wide_chars_whole() {
+        let mut grid = Grid::new(3, 5, 100);
+        print(&mut grid, "abcd世");
+        assert_eq!(screen(&grid), ["abcd", "世", ""]);
+        grid.resize(3, 6, true);
+        assert_eq!(screen(&grid), ["abcd世", "", ""]);
+        grid.resize(3, 3, true);
+        assert_eq!(screen(&grid), ["abc", "d世", ""]);
+        grid.resize(3, 5, true);
+        assert_eq!(screen(&grid), ["abcd", "世", ""]);
+        assert!(is_padding(&grid.visible_row(0, 0).cells));
+    }
+}
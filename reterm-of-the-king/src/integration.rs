@@ -0,0 +1,97 @@
+// Shell integration: scripts that make bash, zsh and fish mark prompts,
+// commands and exit statuses with OSC 133, and how to start each shell so it
+// loads them next to the user's own startup files.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const BASH: &str = include_str!("shell/reterm.bash");
+const ZSH: &str = include_str!("shell/reterm.zsh");
+const ZSHENV: &str = include_str!("shell/zshenv");
+const FISH: &str = include_str!("shell/reterm.fish");
+
+// What to add to the shell's command line and environment.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Launch {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+// Where the scripts are written out for shells to read:
+// `$XDG_DATA_HOME/reterm-of-the-king/shell` (or `~/.local/share/...`).
+pub fn scripts_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("reterm-of-the-king/shell"))
+}
+
+fn write(path: &Path, contents: &str) -> io::Result<String> {
+    fs::create_dir_all(path.parent().expect("script path has a directory"))?;
+    fs::write(path, contents)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// Write the scripts for `shell` (a path or name) under `dir` and say how to
+// start it with them; None for shells without integration.
+pub fn prepare(shell: &str, dir: &Path) -> io::Result<Option<Launch>> {
+    let name = Path::new(shell).file_name().and_then(|n| n.to_str()).unwrap_or(shell);
+    let launch = match name {
+        // --rcfile replaces ~/.bashrc, which the script sources itself
+        "bash" => Launch {
+            args: vec!["--rcfile".to_owned(), write(&dir.join("reterm.bash"), BASH)?],
+            env: Vec::new(),
+        },
+        // zsh has no option for an extra startup file, so it reads our
+        // .zshenv through ZDOTDIR, which then puts the user's back
+        "zsh" => {
+            let zdotdir = dir.join("zsh");
+            write(&zdotdir.join("reterm.zsh"), ZSH)?;
+            write(&zdotdir.join(".zshenv"), ZSHENV)?;
+            let mut env = vec![("ZDOTDIR".to_owned(), zdotdir.to_string_lossy().into_owned())];
+            if let Ok(user) = std::env::var("ZDOTDIR") {
+                env.push(("RETERM_ZDOTDIR".to_owned(), user));
+            }
+            Launch { args: Vec::new(), env }
+        }
+        "fish" => {
+            let path = write(&dir.join("reterm.fish"), FISH)?;
+            let quoted = path.replace('\\', "\\\\").replace('\'', "\\'");
+            Launch {
+                args: vec!["--init-command".to_owned(), format!("source '{quoted}'")],
+                env: Vec::new(),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(launch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_per_shell() {
+        let dir = std::env::temp_dir().join(format!("reterm-integration-{}", std::process::id()));
+        let path = |p: &str| dir.join(p).to_string_lossy().into_owned();
+
+        let bash = prepare("/usr/bin/bash", &dir).unwrap().unwrap();
+        assert_eq!(bash.args, ["--rcfile".to_owned(), path("reterm.bash")]);
+        assert_eq!(fs::read_to_string(dir.join("reterm.bash")).unwrap(), BASH);
+
+        let zsh = prepare("zsh", &dir).unwrap().unwrap();
+        assert!(zsh.args.is_empty());
+        assert_eq!(zsh.env[0], ("ZDOTDIR".to_owned(), path("zsh")));
+        assert!(dir.join("zsh/.zshenv").is_file() && dir.join("zsh/reterm.zsh").is_file());
+
+        let fish = prepare("/usr/local/bin/fish", &dir).unwrap().unwrap();
+        assert_eq!(fish.args[1], format!("source '{}'", path("reterm.fish")));
+
+        assert_eq!(prepare("/bin/dash", &dir).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,551 @@
+// Key presses → bytes for the PTY, following xterm's conventions. Printable
+// text arrives separately as `egui::Event::Text`; this only covers keys that
+// need an encoding of their own.
+
+use eframe::egui::{Key, Modifiers};
+
+// Terminal modes that change what keys send.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyModes {
+    // DECCKM: cursor keys send SS3 (`ESC O A`) instead of CSI (`ESC [ A`)
+    pub app_cursor: bool,
+    // DECKPAM: the numeric keypad sends SS3 sequences instead of digits
+    pub app_keypad: bool,
+    // xterm's modifyOtherKeys level (`CSI > 4 ; n m`); only 2 changes anything
+    pub modify_other_keys: u8,
+    // Kitty keyboard protocol flags (`KITTY_*`); 0 means legacy encoding
+    pub kitty_flags: u8,
+}
+
+// Progressive enhancement flags of the kitty keyboard protocol.
+pub const KITTY_DISAMBIGUATE: u8 = 1;
+pub const KITTY_REPORT_EVENTS: u8 = 2;
+pub const KITTY_REPORT_ALTERNATES: u8 = 4;
+pub const KITTY_REPORT_ALL: u8 = 8;
+pub const KITTY_REPORT_TEXT: u8 = 16;
+pub const KITTY_ALL_FLAGS: u8 = 31;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Press,
+    Repeat,
+    Release,
+}
+
+// xterm's modifier parameter: 1 + shift + 2·alt + 4·ctrl.
+fn modifier_param(mods: Modifiers) -> u8 {
+    1 + mods.shift as u8 + 2 * mods.alt as u8 + 4 * mods.ctrl as u8
+}
+
+fn alt_prefixed(mods: Modifiers, bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 1);
+    if mods.alt {
+        out.push(0x1b);
+    }
+    out.extend_from_slice(bytes);
+    out
+}
+
+// `ESC [ 1 ; m final` when modified, otherwise `ESC [ final` or, in the
+// application mode, `ESC O final`.
+fn cursor_style(final_byte: u8, mods: Modifiers, app: bool) -> Vec<u8> {
+    let m = modifier_param(mods);
+    if m > 1 {
+        format!("\x1b[1;{m}{}", final_byte as char).into_bytes()
+    } else if app {
+        vec![0x1b, b'O', final_byte]
+    } else {
+        vec![0x1b, b'[', final_byte]
+    }
+}
+
+// `ESC [ n ~`, with `; m` appended when modified.
+fn tilde_style(n: u8, mods: Modifiers) -> Vec<u8> {
+    match modifier_param(mods) {
+        1 => format!("\x1b[{n}~").into_bytes(),
+        m => format!("\x1b[{n};{m}~").into_bytes(),
+    }
+}
+
+// The control character Ctrl+`key` produces, if any.
+fn ctrl_byte(key: Key) -> Option<u8> {
+    let letter = key.name().as_bytes();
+    match key {
+        _ if letter.len() == 1 && letter[0].is_ascii_uppercase() => Some(letter[0] & 0x1f),
+        Key::Space | Key::Num2 => Some(0x00),
+        Key::OpenBracket | Key::Num3 => Some(0x1b),
+        Key::Backslash | Key::Num4 => Some(0x1c),
+        Key::CloseBracket | Key::Num5 => Some(0x1d),
+        Key::Num6 => Some(0x1e),
+        Key::Minus | Key::Slash | Key::Num7 => Some(0x1f),
+        Key::Questionmark | Key::Num8 => Some(0x7f),
+        _ => None,
+    }
+}
+
+// Application keypad (DECKPAM) sequences, `ESC O x`.
+fn keypad_app_byte(key: Key) -> Option<u8> {
+    Some(match key {
+        Key::Num0 => b'p',
+        Key::Num1 => b'q',
+        Key::Num2 => b'r',
+        Key::Num3 => b's',
+        Key::Num4 => b't',
+        Key::Num5 => b'u',
+        Key::Num6 => b'v',
+        Key::Num7 => b'w',
+        Key::Num8 => b'x',
+        Key::Num9 => b'y',
+        Key::Period => b'n',
+        Key::Enter => b'M',
+        Key::Plus => b'k',
+        Key::Minus => b'm',
+        Key::Slash => b'o',
+        _ => return None,
+    })
+}
+
+// modifyOtherKeys level 2: modified keys that type a character, and Enter,
+// Backspace, Tab and Escape, as `CSI 27 ; m ; code ~`. Shift alone still
+// just types (and Shift-Tab stays CSI Z).
+fn modify_other(key: Key, mods: Modifiers) -> Option<Vec<u8>> {
+    let m = modifier_param(mods);
+    let code = match key {
+        Key::Enter => 13,
+        Key::Backspace => 127,
+        Key::Escape => 27,
+        Key::Tab if m > 2 => 9,
+        // xterm reports the shifted character
+        _ if m > 2 => {
+            let c = key_char(key)?;
+            (if mods.shift { c.to_ascii_uppercase() } else { c }) as u32
+        }
+        _ => return None,
+    };
+    (m > 1).then(|| format!("\x1b[27;{m};{code}~").into_bytes())
+}
+
+// Bytes for a key press, or None when the key is left to text input (plain
+// printable keys) or has no terminal meaning. `keypad` says the key came
+// from the numeric keypad; egui 0.27 folds those into the main keys, so the
+// app cannot tell yet and always passes false.
+pub fn encode_key(key: Key, mods: Modifiers, keypad: bool, modes: KeyModes) -> Option<Vec<u8>> {
+    if keypad && modes.app_keypad
+        && let Some(b) = keypad_app_byte(key)
+    {
+        return Some(vec![0x1b, b'O', b]);
+    }
+    if modes.modify_other_keys == 2
+        && let Some(bytes) = modify_other(key, mods)
+    {
+        return Some(bytes);
+    }
+
+    let bytes = match key {
+        Key::ArrowUp => cursor_style(b'A', mods, modes.app_cursor),
+        Key::ArrowDown => cursor_style(b'B', mods, modes.app_cursor),
+        Key::ArrowRight => cursor_style(b'C', mods, modes.app_cursor),
+        Key::ArrowLeft => cursor_style(b'D', mods, modes.app_cursor),
+        Key::Home => cursor_style(b'H', mods, modes.app_cursor),
+        Key::End => cursor_style(b'F', mods, modes.app_cursor),
+        Key::Insert => tilde_style(2, mods),
+        Key::Delete => tilde_style(3, mods),
+        Key::PageUp => tilde_style(5, mods),
+        Key::PageDown => tilde_style(6, mods),
+        // F1-F4 are SS3 P..S, or CSI 1;m P..S when modified
+        Key::F1 => cursor_style(b'P', mods, true),
+        Key::F2 => cursor_style(b'Q', mods, true),
+        Key::F3 => cursor_style(b'R', mods, true),
+        Key::F4 => cursor_style(b'S', mods, true),
+        Key::F5 => tilde_style(15, mods),
+        Key::F6 => tilde_style(17, mods),
+        Key::F7 => tilde_style(18, mods),
+        Key::F8 => tilde_style(19, mods),
+        Key::F9 => tilde_style(20, mods),
+        Key::F10 => tilde_style(21, mods),
+        Key::F11 => tilde_style(23, mods),
+        Key::F12 => tilde_style(24, mods),
+        Key::Enter => alt_prefixed(mods, b"\r"),
+        Key::Tab if mods.shift => b"\x1b[Z".to_vec(),
+        Key::Tab => alt_prefixed(mods, b"\t"),
+        Key::Backspace if mods.ctrl => alt_prefixed(mods, &[0x08]),
+        Key::Backspace => alt_prefixed(mods, &[0x7f]),
+        Key::Escape => alt_prefixed(mods, &[0x1b]),
+        _ if mods.ctrl => alt_prefixed(mods, &[ctrl_byte(key)?]),
+        // Alt+printable is handled with the text it produces
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+// The unshifted character a key types, for keys that type one.
+fn key_char(key: Key) -> Option<char> {
+    match key {
+        Key::Space => Some(' '),
+        // egui names it with U+2212
+        Key::Minus => Some('-'),
+        _ => {
+            let mut chars = key.symbol_or_name().chars();
+            let c = chars.next()?;
+            (chars.next().is_none() && c.is_ascii_graphic()).then(|| c.to_ascii_lowercase())
+        }
+    }
+}
+
+// Kitty's number and final byte for a key: `CSI number u` for text keys and
+// a few others, the legacy `CSI 1 A` / `CSI n ~` forms for the rest.
+fn kitty_code(key: Key) -> Option<(u32, u8)> {
+    let n = key as u32;
+    Some(match key {
+        Key::Escape => (27, b'u'),
+        Key::Enter => (13, b'u'),
+        Key::Tab => (9, b'u'),
+        Key::Backspace => (127, b'u'),
+        Key::Insert => (2, b'~'),
+        Key::Delete => (3, b'~'),
+        Key::PageUp => (5, b'~'),
+        Key::PageDown => (6, b'~'),
+        Key::ArrowUp => (1, b'A'),
+        Key::ArrowDown => (1, b'B'),
+        Key::ArrowRight => (1, b'C'),
+        Key::ArrowLeft => (1, b'D'),
+        Key::Home => (1, b'H'),
+        Key::End => (1, b'F'),
+        Key::F1 => (1, b'P'),
+        Key::F2 => (1, b'Q'),
+        // CSI R would read as a cursor position report
+        Key::F3 => (13, b'~'),
+        Key::F4 => (1, b'S'),
+        Key::F5 => (15, b'~'),
+        Key::F6 => (17, b'~'),
+        Key::F7 => (18, b'~'),
+        Key::F8 => (19, b'~'),
+        Key::F9 => (20, b'~'),
+        Key::F10 => (21, b'~'),
+        Key::F11 => (23, b'~'),
+        Key::F12 => (24, b'~'),
+        // F13 and up live in the private use area
+        _ if (Key::F13 as u32..=Key::F35 as u32).contains(&n) => (n - Key::F13 as u32 + 57376, b'u'),
+        _ => (key_char(key)? as u32, b'u'),
+    })
+}
+
+// Like `modifier_param`, plus super (bit 8), which kitty also reports.
+fn kitty_modifiers(mods: Modifiers) -> u8 {
+    modifier_param(mods) + 8 * mods.mac_cmd as u8
+}
+
+// Bytes for a key event under the kitty keyboard protocol, given the active
+// `modes.kitty_flags`. `text` is what the key typed, if anything. Keys the
+// enabled flags leave alone fall back to `encode_key`, or to text input.
+pub fn encode_kitty(key: Key, mods: Modifiers, action: KeyAction, text: Option<&str>, modes: KeyModes) -> Option<Vec<u8>> {
+    let flags = modes.kitty_flags;
+    let action = match action {
+        _ if flags & KITTY_REPORT_EVENTS != 0 => action,
+        KeyAction::Release => return None,
+        _ => KeyAction::Press,
+    };
+    let legacy = || match action {
+        KeyAction::Release => None,
+        _ => encode_key(key, mods, false, modes),
+    };
+    if flags & (KITTY_DISAMBIGUATE | KITTY_REPORT_ALL) == 0 {
+        return legacy();
+    }
+
+    let (code, final_byte) = kitty_code(key)?;
+    let plain = !(mods.ctrl || mods.alt || mods.mac_cmd);
+    if flags & KITTY_REPORT_ALL == 0 {
+        // Text keys still just type, and unmodified Enter, Tab and
+        // Backspace keep their bytes so a shell stays usable.
+        if key_char(key).is_some() && plain {
+            return None;
+        }
+        if matches!(key, Key::Enter | Key::Tab | Key::Backspace) && plain && !mods.shift {
+            return legacy();
+        }
+    }
+
+    let m = kitty_modifiers(mods);
+    let event = match action {
+        KeyAction::Press => 1,
+        KeyAction::Repeat => 2,
+        KeyAction::Release => 3,
+    };
+    let text = match text {
+        Some(t) if flags & KITTY_REPORT_ALL != 0 && flags & KITTY_REPORT_TEXT != 0 && !t.is_empty() => {
+            let codepoints: Vec<String> = t.chars().map(|c| (c as u32).to_string()).collect();
+            Some(codepoints.join(":"))
+        }
+        _ => None,
+    };
+
+    let mut out = String::from("\x1b[");
+    let more = m > 1 || event > 1 || text.is_some();
+    if code != 1 || final_byte == b'u' || more {
+        out += &code.to_string();
+    }
+    // the shifted key, which only letters let us know without a keymap
+    if flags & KITTY_REPORT_ALTERNATES != 0
+        && mods.shift
+        && final_byte == b'u'
+        && let Some(c) = key_char(key).filter(|c| c.is_ascii_lowercase())
+    {
+        out += &format!(":{}", c.to_ascii_uppercase() as u32);
+    }
+    if more {
+        out += &format!(";{m}");
+        if event > 1 {
+            out += &format!(":{event}");
+        }
+    }
+    if let Some(text) = text {
+        out += &format!(";{text}");
+    }
+    out.push(final_byte as char);
+    Some(out.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: Modifiers = Modifiers::NONE;
+    const SHIFT: Modifiers = Modifiers::SHIFT;
+    const CTRL: Modifiers = Modifiers::CTRL;
+    const ALT: Modifiers = Modifiers::ALT;
+
+    fn mods(shift: bool, alt: bool, ctrl: bool) -> Modifiers {
+        Modifiers {
+            shift,
+            alt,
+            ctrl,
+            command: ctrl,
+            ..NONE
+        }
+    }
+
+    #[test]
+    fn normal_mode_table() {
+        let modes = KeyModes::default();
+        let table: &[(Key, Modifiers, &[u8])] = &[
+            (Key::Enter, NONE, b"\r"),
+            (Key::Enter, ALT, b"\x1b\r"),
+            (Key::Tab, NONE, b"\t"),
+            (Key::Tab, SHIFT, b"\x1b[Z"),
+            (Key::Backspace, NONE, b"\x7f"),
+            (Key::Backspace, CTRL, b"\x08"),
+            (Key::Backspace, ALT, b"\x1b\x7f"),
+            (Key::Escape, NONE, b"\x1b"),
+            (Key::ArrowUp, NONE, b"\x1b[A"),
+            (Key::ArrowDown, NONE, b"\x1b[B"),
+            (Key::ArrowRight, NONE, b"\x1b[C"),
+            (Key::ArrowLeft, NONE, b"\x1b[D"),
+            (Key::ArrowUp, CTRL, b"\x1b[1;5A"),
+            (Key::ArrowLeft, SHIFT, b"\x1b[1;2D"),
+            (Key::ArrowRight, mods(true, true, true), b"\x1b[1;8C"),
+            (Key::Home, NONE, b"\x1b[H"),
+            (Key::End, NONE, b"\x1b[F"),
+            (Key::End, CTRL, b"\x1b[1;5F"),
+            (Key::Insert, NONE, b"\x1b[2~"),
+            (Key::Delete, NONE, b"\x1b[3~"),
+            (Key::Delete, ALT, b"\x1b[3;3~"),
+            (Key::PageUp, NONE, b"\x1b[5~"),
+            (Key::PageDown, CTRL, b"\x1b[6;5~"),
+            (Key::F1, NONE, b"\x1bOP"),
+            (Key::F4, NONE, b"\x1bOS"),
+            (Key::F1, SHIFT, b"\x1b[1;2P"),
+            (Key::F5, NONE, b"\x1b[15~"),
+            (Key::F6, NONE, b"\x1b[17~"),
+            (Key::F10, NONE, b"\x1b[21~"),
+            (Key::F11, NONE, b"\x1b[23~"),
+            (Key::F12, NONE, b"\x1b[24~"),
+            (Key::F12, CTRL, b"\x1b[24;5~"),
+            (Key::A, CTRL, b"\x01"),
+            (Key::C, CTRL, b"\x03"),
+            (Key::Z, CTRL, b"\x1a"),
+            (Key::C, mods(false, true, true), b"\x1b\x03"),
+            (Key::Space, CTRL, b"\x00"),
+            (Key::OpenBracket, CTRL, b"\x1b"),
+            (Key::Backslash, CTRL, b"\x1c"),
+            (Key::CloseBracket, CTRL, b"\x1d"),
+            (Key::Num6, CTRL, b"\x1e"),
+            (Key::Minus, CTRL, b"\x1f"),
+        ];
+        for (key, m, expected) in table {
+            assert_eq!(
+                encode_key(*key, *m, false, modes).as_deref(),
+                Some(*expected),
+                "{key:?} with {m:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn application_cursor_mode_table() {
+        let modes = KeyModes {
+            app_cursor: true,
+            ..KeyModes::default()
+        };
+        let table: &[(Key, Modifiers, &[u8])] = &[
+            (Key::ArrowUp, NONE, b"\x1bOA"),
+            (Key::ArrowLeft, NONE, b"\x1bOD"),
+            (Key::Home, NONE, b"\x1bOH"),
+            (Key::End, NONE, b"\x1bOF"),
+            // modified keys keep the CSI form
+            (Key::ArrowUp, CTRL, b"\x1b[1;5A"),
+        ];
+        for (key, m, expected) in table {
+            let got = encode_key(*key, *m, false, modes);
+            assert_eq!(got.as_deref(), Some(*expected), "{key:?}");
+        }
+    }
+
+    #[test]
+    fn application_keypad_mode_table() {
+        let app = KeyModes {
+            app_keypad: true,
+            ..KeyModes::default()
+        };
+        let table: &[(Key, &[u8])] = &[
+            (Key::Num0, b"\x1bOp"),
+            (Key::Num9, b"\x1bOy"),
+            (Key::Enter, b"\x1bOM"),
+            (Key::Plus, b"\x1bOk"),
+            (Key::Period, b"\x1bOn"),
+        ];
+        for (key, expected) in table {
+            let got = encode_key(*key, NONE, true, app);
+            assert_eq!(got.as_deref(), Some(*expected), "{key:?}");
+        }
+        // without DECKPAM the keypad types like the main keys
+        assert_eq!(encode_key(Key::Num5, NONE, true, KeyModes::default()), None);
+        let enter = encode_key(Key::Enter, NONE, true, KeyModes::default());
+        assert_eq!(enter.as_deref(), Some(&b"\r"[..]));
+    }
+
+    #[test]
+    fn printable_keys_are_left_to_text_input() {
+        let modes = KeyModes::default();
+        for key in [Key::A, Key::Num1, Key::Space, Key::Minus] {
+            assert_eq!(encode_key(key, NONE, false, modes), None, "{key:?}");
+            assert_eq!(encode_key(key, ALT, false, modes), None, "{key:?}");
+        }
+    }
+
+    fn kitty(flags: u8) -> KeyModes {
+        KeyModes {
+            kitty_flags: flags,
+            ..KeyModes::default()
+        }
+    }
+
+    #[test]
+    fn kitty_disambiguate_table() {
+        let modes = kitty(KITTY_DISAMBIGUATE);
+        let table: &[(Key, Modifiers, Option<&[u8]>)] = &[
+            // Ctrl-I and Tab no longer look alike
+            (Key::I, CTRL, Some(b"\x1b[105;5u")),
+            (Key::Tab, NONE, Some(b"\t")),
+            (Key::Tab, SHIFT, Some(b"\x1b[9;2u")),
+            (Key::Enter, NONE, Some(b"\r")),
+            (Key::Enter, CTRL, Some(b"\x1b[13;5u")),
+            (Key::Backspace, NONE, Some(b"\x7f")),
+            (Key::Escape, NONE, Some(b"\x1b[27u")),
+            (Key::A, ALT, Some(b"\x1b[97;3u")),
+            (Key::A, mods(true, false, true), Some(b"\x1b[97;6u")),
+            (Key::OpenBracket, CTRL, Some(b"\x1b[91;5u")),
+            (Key::Minus, CTRL, Some(b"\x1b[45;5u")),
+            (Key::ArrowUp, NONE, Some(b"\x1b[A")),
+            (Key::ArrowUp, CTRL, Some(b"\x1b[1;5A")),
+            (Key::F1, NONE, Some(b"\x1b[P")),
+            (Key::F3, NONE, Some(b"\x1b[13~")),
+            (Key::F3, SHIFT, Some(b"\x1b[13;2~")),
+            (Key::Delete, NONE, Some(b"\x1b[3~")),
+            (Key::F13, NONE, Some(b"\x1b[57376u")),
+            // plain and shifted text is left to text input
+            (Key::A, NONE, None),
+            (Key::A, SHIFT, None),
+            (Key::Space, NONE, None),
+        ];
+        for (key, m, expected) in table {
+            let got = encode_kitty(*key, *m, KeyAction::Press, None, modes);
+            assert_eq!(got.as_deref(), *expected, "{key:?} with {m:?}");
+        }
+    }
+
+    #[test]
+    fn kitty_event_types() {
+        let press_only = kitty(KITTY_DISAMBIGUATE);
+        assert_eq!(encode_kitty(Key::I, CTRL, KeyAction::Release, None, press_only), None);
+        let repeat = encode_kitty(Key::I, CTRL, KeyAction::Repeat, None, press_only);
+        assert_eq!(repeat.as_deref(), Some(&b"\x1b[105;5u"[..]));
+
+        let modes = kitty(KITTY_DISAMBIGUATE | KITTY_REPORT_EVENTS);
+        let table: &[(Key, Modifiers, KeyAction, Option<&[u8]>)] = &[
+            (Key::I, CTRL, KeyAction::Repeat, Some(b"\x1b[105;5:2u")),
+            (Key::I, CTRL, KeyAction::Release, Some(b"\x1b[105;5:3u")),
+            (Key::ArrowLeft, NONE, KeyAction::Release, Some(b"\x1b[1;1:3D")),
+            (Key::Escape, NONE, KeyAction::Release, Some(b"\x1b[27;1:3u")),
+            // no release events for keys that were sent as text or legacy bytes
+            (Key::A, NONE, KeyAction::Release, None),
+            (Key::Enter, NONE, KeyAction::Release, None),
+        ];
+        for (key, m, action, expected) in table {
+            let got = encode_kitty(*key, *m, *action, None, modes);
+            assert_eq!(got.as_deref(), *expected, "{key:?} {action:?}");
+        }
+    }
+
+    #[test]
+    fn kitty_report_all_keys() {
+        let modes = kitty(KITTY_REPORT_ALL | KITTY_REPORT_EVENTS | KITTY_REPORT_ALTERNATES | KITTY_REPORT_TEXT);
+        type Case<'a> = (Key, Modifiers, KeyAction, Option<&'a str>, &'a [u8]);
+        let table: &[Case] = &[
+            (Key::A, NONE, KeyAction::Press, Some("a"), b"\x1b[97;1;97u"),
+            (Key::A, SHIFT, KeyAction::Press, Some("A"), b"\x1b[97:65;2;65u"),
+            (Key::A, NONE, KeyAction::Release, None, b"\x1b[97;1:3u"),
+            (Key::Enter, NONE, KeyAction::Press, None, b"\x1b[13u"),
+            (Key::Enter, NONE, KeyAction::Release, None, b"\x1b[13;1:3u"),
+            (Key::Backspace, NONE, KeyAction::Press, None, b"\x1b[127u"),
+        ];
+        for (key, m, action, text, expected) in table {
+            let got = encode_kitty(*key, *m, *action, *text, modes);
+            assert_eq!(got.as_deref(), Some(*expected), "{key:?} {action:?}");
+        }
+    }
+
+    #[test]
+    fn kitty_without_disambiguation_keeps_legacy_bytes() {
+        let modes = kitty(KITTY_REPORT_EVENTS);
+        let got = encode_kitty(Key::I, CTRL, KeyAction::Press, None, modes);
+        assert_eq!(got.as_deref(), Some(&b"\x09"[..]));
+        assert_eq!(encode_kitty(Key::I, CTRL, KeyAction::Release, None, modes), None);
+    }
+
+    #[test]
+    fn modify_other_keys_table() {
+        let modes = KeyModes {
+            modify_other_keys: 2,
+            ..KeyModes::default()
+        };
+        let table: &[(Key, Modifiers, Option<&[u8]>)] = &[
+            (Key::A, CTRL, Some(b"\x1b[27;5;97~")),
+            (Key::A, mods(true, false, true), Some(b"\x1b[27;6;65~")),
+            (Key::A, ALT, Some(b"\x1b[27;3;97~")),
+            (Key::Num1, CTRL, Some(b"\x1b[27;5;49~")),
+            (Key::Enter, CTRL, Some(b"\x1b[27;5;13~")),
+            (Key::Enter, SHIFT, Some(b"\x1b[27;2;13~")),
+            (Key::Tab, CTRL, Some(b"\x1b[27;5;9~")),
+            (Key::Enter, NONE, Some(b"\r")),
+            (Key::Tab, SHIFT, Some(b"\x1b[Z")),
+            (Key::ArrowUp, CTRL, Some(b"\x1b[1;5A")),
+            (Key::A, NONE, None),
+            (Key::A, SHIFT, None),
+        ];
+        for (key, m, expected) in table {
+            let got = encode_key(*key, *m, false, modes);
+            assert_eq!(got.as_deref(), *expected, "{key:?} with {m:?}");
+        }
+    }
+}
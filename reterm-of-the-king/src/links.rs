@@ -0,0 +1,319 @@
+// Hyperlinks on screen, explicit (OSC 8) or spotted in the text, and
+// opening them.
+
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+    thread,
+};
+
+use regex::Regex;
+
+use crate::{grid::Flags, selection::Point, term::Term};
+
+// Opens link targets. Pluggable so tests can see what would be opened
+// without starting anything.
+pub trait Opener {
+    // Open a URL with the desktop's default handler.
+    fn open(&self, url: &str) {
+        self.run(&["xdg-open".to_owned(), url.to_owned()]);
+    }
+
+    // Start a program in the background.
+    fn run(&self, argv: &[String]);
+}
+
+pub struct XdgOpen;
+
+impl Opener for XdgOpen {
+    fn run(&self, argv: &[String]) {
+        let Some((program, args)) = argv.split_first() else {
+            return;
+        };
+        match Command::new(program).args(args).spawn() {
+            // reap it when done; the SIGCHLD watcher only waits for the shell
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => eprintln!("{program}: {e}"),
+        }
+    }
+}
+
+// Schemes handed to the opener. The application picks OSC 8 targets freely,
+// so anything else is ignored.
+const OPEN_SCHEMES: [&str; 5] = ["http", "https", "ftp", "file", "mailto"];
+
+// Where a link shows in the viewport: (row, columns) runs of its cells.
+pub type Spans = Vec<(usize, Range<usize>)>;
+
+// The link (as numbered in cells) at `at`, or 0.
+pub fn link_at(term: &Term, at: Point) -> u32 {
+    let row = term.grid.line(at.line);
+    row.and_then(|r| r.cells.get(at.col)).map_or(0, |c| c.link)
+}
+
+// Open the link under `at`; returns whether there was one to open.
+pub fn open_link_at(term: &Term, at: Point, opener: &dyn Opener) -> bool {
+    let Some(link) = term.hyperlink(link_at(term, at)) else {
+        return false;
+    };
+    let scheme = link.uri.split_once(':').map(|(s, _)| s.to_ascii_lowercase());
+    if !scheme.is_some_and(|s| OPEN_SCHEMES.contains(&s.as_str())) {
+        eprintln!("not opening {:?}: unsupported scheme", link.uri);
+        return false;
+    }
+    opener.open(&link.uri);
+    true
+}
+
+// Where `link` shows in the viewport.
+pub fn link_spans(term: &Term, scroll_offset: usize, link: u32) -> Spans {
+    let mut spans = Vec::new();
+    if link == 0 {
+        return spans;
+    }
+    for r in 0..term.grid.rows() {
+        let cells = &term.grid.visible_row(scroll_offset, r).cells;
+        let mut col = 0;
+        while col < cells.len() {
+            if cells[col].link != link {
+                col += 1;
+                continue;
+            }
+            let len = cells[col..].iter().take_while(|c| c.link == link).count();
+            spans.push((r, col..col + len));
+            col += len;
+        }
+    }
+    spans
+}
+
+// Something worth opening found in the text itself.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    Url(String),
+    // a file that exists, with the position compiler-style references give
+    File { path: PathBuf, line: u32, col: Option<u32> },
+}
+
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`]+"#).unwrap());
+// `src/foo.rs:12:5`, `./x.py:3`, `~/notes.txt:10`
+static FILE_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:~/|\.{0,2}/)?[\w.+-]+(?:/[\w.+-]+)*:(\d+)(?::(\d+))?").unwrap());
+
+// Drop punctuation that more likely ends the sentence than the URL.
+fn trim_url(url: &str) -> &str {
+    let mut url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    while url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+        url = url[..url.len() - 1].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    }
+    url
+}
+
+// `path` as an existing file, relative paths taken from `cwd`.
+fn resolve_path(path: &str, cwd: &Path) -> Option<PathBuf> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => cwd.join(path),
+    };
+    path.is_file().then_some(path)
+}
+
+// The URL or file reference at viewport cell (`row`, `col`), with the cells
+// it covers. Lines soft-wrapped across rows are searched as one.
+pub fn detect_at(term: &Term, scroll_offset: usize, row: usize, col: usize, cwd: &Path) -> Option<(Target, Spans)> {
+    let grid = &term.grid;
+    let visible = |r| grid.visible_row(scroll_offset, r);
+    let mut first = row;
+    while first > 0 && visible(first - 1).wrapped {
+        first -= 1;
+    }
+    let mut last = row;
+    while last + 1 < grid.rows() && visible(last).wrapped {
+        last += 1;
+    }
+    let mut text = String::new();
+    // the cell each char of `text` came from, by byte offset
+    let mut cells = Vec::new();
+    for r in first..=last {
+        for (c, cell) in visible(r).cells.iter().enumerate() {
+            text.extend(cell.text());
+            cells.resize(text.len(), (r, c));
+        }
+    }
+    // the right half of a wide character shows the left one
+    let col = match visible(row).cells.get(col) {
+        Some(cell) if col > 0 && cell.flags.contains(Flags::SPACER) => col - 1,
+        _ => col,
+    };
+    let hit = cells.iter().position(|&p| p == (row, col))?;
+
+    let url = URL
+        .find_iter(&text)
+        .map(|m| (m.start(), m.start() + trim_url(m.as_str()).len(), None))
+        .find(|&(start, end, _)| (start..end).contains(&hit));
+    let found = url.or_else(|| {
+        FILE_LINE
+            .captures_iter(&text)
+            .map(|c| {
+                let m = c.get(0).unwrap();
+                (m.start(), m.end(), Some(c))
+            })
+            .find(|&(start, end, _)| (start..end).contains(&hit))
+    });
+    let (start, end, captures) = found?;
+
+    let target = match captures {
+        None => Target::Url(text[start..end].to_owned()),
+        Some(c) => {
+            let path = &text[start..c.get(1)?.start() - 1];
+            Target::File {
+                path: resolve_path(path, cwd)?,
+                line: c[1].parse().ok()?,
+                col: c.get(2).and_then(|m| m.as_str().parse().ok()),
+            }
+        }
+    };
+
+    let mut spans = Spans::new();
+    for &(r, c) in cells[start..end].iter() {
+        match spans.last_mut() {
+            Some((last_row, cols)) if *last_row == r => cols.end = c + 1,
+            _ => spans.push((r, c..c + 1)),
+        }
+    }
+    Some((target, spans))
+}
+
+// Open a detected target: URLs with the desktop's handler, files with
+// `editor` (an argv where `{file}`, `{line}` and `{col}` are filled in) or,
+// lacking one, like a `file://` URL.
+pub fn open_target(target: &Target, editor: Option<&[String]>, opener: &dyn Opener) {
+    match (target, editor) {
+        (Target::Url(url), _) => opener.open(url),
+        (Target::File { path, line, col }, Some(editor)) => {
+            let file = path.to_string_lossy();
+            let argv: Vec<String> = editor
+                .iter()
+                .map(|arg| {
+                    arg.replace("{file}", &file)
+                        .replace("{line}", &line.to_string())
+                        .replace("{col}", &col.unwrap_or(1).to_string())
+                })
+                .collect();
+            opener.run(&argv);
+        }
+        (Target::File { path, .. }, None) => opener.open(&format!("file://{}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    // Records what would have been started, space-separated.
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl Opener for Recorder {
+        fn run(&self, argv: &[String]) {
+            self.0.borrow_mut().push(argv.join(" "));
+        }
+    }
+
+    fn at(line: usize, col: usize) -> Point {
+        Point { line, col }
+    }
+
+    #[test]
+    fn ctrl_click_opens_the_link_under_the_pointer() {
+        let mut term = Term::new(4, 30, 0);
+        term.feed(b"see \x1b]8;;https://example.com/docs\x07the docs\x1b]8;;\x07 here\r\n");
+        term.feed(b"\x1b]8;;javascript:alert(1)\x07bad\x1b]8;;\x07");
+        let opener = Recorder::default();
+
+        assert!(!open_link_at(&term, at(0, 2), &opener));
+        assert!(open_link_at(&term, at(0, 6), &opener));
+        assert!(!open_link_at(&term, at(1, 0), &opener));
+        assert_eq!(*opener.0.borrow(), ["xdg-open https://example.com/docs"]);
+    }
+
+    #[test]
+    fn spans_cover_every_run_of_a_link() {
+        let mut term = Term::new(3, 20, 0);
+        term.feed(b"\x1b]8;id=x;https://a\x07ab\x1b]8;;\x07 cd \x1b]8;id=x;https://a\x07ef\x1b]8;;\x07\r\n");
+        term.feed(b"\x1b]8;id=x;https://a\x07gh\x1b]8;;\x07");
+        let link = link_at(&term, at(0, 0));
+        assert_eq!(link_spans(&term, 0, link), [(0, 0..2), (0, 6..8), (1, 0..2)]);
+        assert!(link_spans(&term, 0, 0).is_empty());
+    }
+
+    // The target at (row, col) of a 6×40 terminal showing `output`.
+    fn detect(output: &str, row: usize, col: usize, cwd: &Path) -> Option<(Target, Spans)> {
+        let mut term = Term::new(6, 40, 0);
+        term.feed(output.as_bytes());
+        detect_at(&term, 0, row, col, cwd)
+    }
+
+    fn url(s: &str) -> Target {
+        Target::Url(s.to_owned())
+    }
+
+    #[test]
+    fn detects_urls() {
+        let cwd = Path::new("/nonexistent");
+        let table: &[(&str, usize, Option<Target>)] = &[
+            ("see https://example.com/a?b=c.", 6, Some(url("https://example.com/a?b=c"))),
+            ("(docs at http://x.org/wiki/Foo_(bar)).", 12, Some(url("http://x.org/wiki/Foo_(bar)"))),
+            ("<ftp://files.example/pub>", 3, Some(url("ftp://files.example/pub"))),
+            ("no link here", 3, None),
+            ("see https://example.com", 1, None),
+        ];
+        for (output, col, expected) in table {
+            let got = detect(output, 0, *col, cwd).map(|(target, _)| target);
+            assert_eq!(got, *expected, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn urls_wrapped_across_rows_are_one_link() {
+        let long = format!("x https://example.com/{}", "a".repeat(40));
+        let (target, spans) = detect(&long, 1, 5, Path::new("/")).unwrap();
+        assert_eq!(target, url(&long[2..]));
+        assert_eq!(spans, [(0, 2..40), (1, 0..22)]);
+    }
+
+    #[test]
+    fn detects_existing_files_with_positions() {
+        let dir = std::env::temp_dir().join(format!("reterm-links-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/foo.rs"), "").unwrap();
+
+        let output = "error: --> src/foo.rs:12:5\r\nmissing.rs:3 localhost:8080";
+        let (target, spans) = detect(output, 0, 14, &dir).unwrap();
+        let file = Target::File {
+            path: dir.join("src/foo.rs"),
+            line: 12,
+            col: Some(5),
+        };
+        assert_eq!(target, file);
+        assert_eq!(spans, [(0, 11..26)]);
+        // references to files that don't exist are just text
+        assert_eq!(detect(output, 1, 2, &dir), None);
+        assert_eq!(detect(output, 1, 16, &dir), None);
+
+        let opener = Recorder::default();
+        let editor = ["code", "--goto", "{file}:{line}:{col}"].map(str::to_owned);
+        open_target(&file, Some(&editor), &opener);
+        open_target(&file, None, &opener);
+        let path = dir.join("src/foo.rs").display().to_string();
+        assert_eq!(*opener.0.borrow(), [format!("code --goto {path}:12:5"), format!("xdg-open file://{path}")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
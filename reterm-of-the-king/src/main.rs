@@ -1,162 +1,576 @@
-use std::{ffi::CString, io::Write, os::unix::io::RawFd, thread, time::Duration};
-
-use crossbeam_channel::{unbounded, Receiver, Sender};
-use eframe::{egui, egui::TextStyle};
-use nix::{
-    pty::{forkpty, Winsize},
-    unistd::{execvp, read, ForkResult, write as nix_write},
-};
-
-fn spawn_shell_pty() -> RawFd {
-    // (Optional) set initial window size for the PTY so apps see a sane rows/cols.
-    let ws = Winsize {
-        ws_row: 24,
-        ws_col: 80,
-        ws_xpixel: 0,
-        ws_ypixel: 0,
-    };
-    match nix::pty::forkpty(&ws, None) {
-        Ok(fork_pty_res) => {
-            let master_fd = fork_pty_res.master;
-            if let ForkResult::Child = fork_pty_res.fork_result {
-                // In child: replace with the shell, inheriting the slave as stdio.
-                let shell = std::env::var("SHELL").unwrap_or("/bin/bash".to_owned());
-                let c = CString::new(shell.clone()).unwrap();
-                execvp(&c, &[c.as_c_str()]).expect("execvp(shell) failed");
+use std::os::unix::io::RawFd;
 
-            }
-            master_fd
-        }
-        Err(e) => panic!("forkpty failed: {e:?}"),
-    }
-}
+use crossbeam_channel::{unbounded, Receiver};
+use eframe::egui;
+use nix::unistd::write as nix_write;
 
-fn start_reader_thread(master_fd: RawFd, tx: Sender<Vec<u8>>) {
-    thread::spawn(move || {
-        let mut buf = [0u8; 8192];
-        loop {
-            match read(master_fd, &mut buf) {
-                Ok(0) => {
-                    let _ = tx.send(b"\n[child closed]\n".to_vec());
-                    break;
-                }
-                Ok(n) => {
-                    let _ = tx.send(buf[..n].to_vec());
-                }
-                Err(_e) => {
-                    thread::sleep(Duration::from_millis(5));
-                }
-            }
-        }
-    });
+mod config;
+mod grid;
+mod integration;
+mod keys;
+mod links;
+mod mouse;
+mod paste;
+mod pty;
+mod render;
+mod scrollback;
+mod selection;
+mod term;
+mod theme;
+
+use config::{ClipboardConfig, Config, OnExit, Osc52Read};
+use grid::MarkKind;
+use keys::KeyAction;
+use links::{Opener, Spans, Target, XdgOpen};
+use mouse::{MouseButton, MouseEvent, MouseTracking};
+use pty::{ExitStatus, PtyEvent};
+use render::Renderer;
+use selection::{Point, Selection, SelectionKind};
+use term::{Term, TermEvent};
+use theme::Theme;
+
+const APP_NAME: &str = "reterm-of-the-king";
+const FONT_SIZE: f32 = 14.0;
+// History kept unless the config says otherwise
+const SCROLLBACK_LINES: usize = 10_000;
+// Longest gap between the clicks of a double or triple click
+const DOUBLE_CLICK_SECS: f64 = 0.4;
+
+// egui only hands out the clipboard on its paste shortcut, so read it directly.
+fn clipboard_text() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut c| c.get_text())
+        .map_err(|e| eprintln!("clipboard: {e}"))
+        .ok()
 }
 
 struct RetermApp {
     master_fd: RawFd,
-    rx: Receiver<Vec<u8>>,
-    buffer: String,
-    // crude scrollback cap to keep memory sane
-    max_len: usize,
+    rx: Receiver<PtyEvent>,
+    term: Term,
+    // set once the reader thread saw the slave side close
+    pty_closed: bool,
+    exit_status: Option<ExitStatus>,
+    on_exit: OnExit,
+    clipboard: ClipboardConfig,
+    // how many lines the viewport is scrolled back into history
+    scroll_offset: usize,
+    renderer: Renderer,
+    // button held and cell last reported while the application tracks the mouse
+    mouse_held: Option<MouseButton>,
+    mouse_cell: Option<(usize, usize)>,
+    selection: Option<Selection>,
+    // where the primary button went down, while it is held for selecting
+    selecting: Option<Point>,
+    // time, place and count of the last click, to spot double/triple clicks
+    last_click: Option<(f64, Point, u8)>,
+    // the OSC 8 link under the pointer (0 for none), and what Ctrl-click
+    // opens links with
+    hovered_link: u32,
+    opener: Box<dyn Opener>,
+    // a URL or file reference under the pointer while Ctrl is held, with
+    // the cells it covers, and the editor command file references open in
+    hovered_target: Option<(Target, Spans)>,
+    editor: Option<Vec<String>>,
+    // the native window title as last set
+    window_title: String,
+    themes: Vec<Theme>,
+    // index into `themes`
+    theme: usize,
 }
 
 impl RetermApp {
-    fn new(master_fd: RawFd, rx: Receiver<Vec<u8>>) -> Self {
+    fn new(master_fd: RawFd, rx: Receiver<PtyEvent>, config: &Config) -> Self {
+        let themes = config.themes();
+        let theme = match &config.theme {
+            Some(name) => themes.iter().position(|t| t.name == *name).unwrap_or_else(|| {
+                eprintln!("config: unknown theme {name:?}");
+                0
+            }),
+            None => 0,
+        };
+        let mut term = Term::new(24, 80, config.scrollback_lines.unwrap_or(SCROLLBACK_LINES));
+        term.osc52_max_bytes = config.clipboard.osc52_max_bytes;
         Self {
             master_fd,
             rx,
-            buffer: String::new(),
-            max_len: 512_000, // ~0.5MB
+            term,
+            pty_closed: false,
+            exit_status: None,
+            on_exit: config.on_exit,
+            clipboard: config.clipboard,
+            scroll_offset: 0,
+            renderer: Renderer::default(),
+            mouse_held: None,
+            mouse_cell: None,
+            selection: None,
+            selecting: None,
+            last_click: None,
+            hovered_link: 0,
+            opener: Box::new(XdgOpen),
+            hovered_target: None,
+            editor: config.links.editor.clone(),
+            window_title: APP_NAME.to_owned(),
+            themes,
+            theme,
         }
     }
 
-    fn pump_rx(&mut self) {
+    fn pump_rx(&mut self, ctx: &egui::Context) {
         // Drain available chunks each frame
-        while let Ok(chunk) = self.rx.try_recv() {
-            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
-            if self.buffer.len() > self.max_len {
-                let cut = self.buffer.len() - self.max_len;
-                self.buffer.drain(..cut);
+        let pushed_before = self.term.grid.scrollback.total();
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                PtyEvent::Output(chunk) => self.term.feed(&chunk),
+                PtyEvent::Closed => self.pty_closed = true,
+                PtyEvent::Exited(status) => {
+                    self.term.feed(format!("\r\n[process {status}]\r\n").as_bytes());
+                    self.exit_status = Some(status);
+                }
             }
         }
+        for event in self.term.take_events() {
+            self.handle_term_event(ctx, event);
+        }
+        let responses = self.term.take_responses();
+        if !responses.is_empty() {
+            self.send_to_pty(&responses);
+        }
+        // Keep a scrolled-back view anchored on the same history lines.
+        if self.scroll_offset > 0 {
+            let pushed = self.term.grid.scrollback.total().saturating_sub(pushed_before);
+            self.scroll_offset = (self.scroll_offset + pushed).min(self.term.grid.scrollback.len());
+        }
+    }
+
+    // Carry out what the application asked for, as far as the config allows.
+    fn handle_term_event(&mut self, ctx: &egui::Context, event: TermEvent) {
+        let max = self.clipboard.osc52_max_bytes;
+        match event {
+            TermEvent::SetClipboard(text) if self.clipboard.osc52_write && text.len() <= max => {
+                ctx.copy_text(text);
+            }
+            TermEvent::SetClipboard(text) => eprintln!("osc52: refused to copy {} bytes", text.len()),
+            TermEvent::ReadClipboard if self.clipboard.osc52_read == Osc52Read::Allow => {
+                if let Some(text) = clipboard_text().filter(|t| t.len() <= max) {
+                    self.term.clipboard_contents(&text);
+                }
+            }
+            TermEvent::ReadClipboard => {}
+        }
     }
 
     fn send_to_pty(&self, bytes: &[u8]) {
+        if self.pty_closed {
+            return;
+        }
         let _ = nix_write(self.master_fd, bytes);
     }
+
+    // Make egui's own widgets follow the terminal theme.
+    fn apply_theme(&self, ctx: &egui::Context) {
+        let theme = &self.themes[self.theme];
+        let mut visuals = ctx.style().visuals.clone();
+        visuals.panel_fill = theme.background;
+        visuals.extreme_bg_color = theme.background;
+        visuals.override_text_color = Some(theme.foreground);
+        visuals.selection.bg_fill = theme.selection;
+        ctx.set_visuals(visuals);
+    }
+
+    // Encode and send a key event; returns whether it produced any bytes.
+    // `text` is what the key typed, for protocols that report it.
+    fn send_key(&mut self, key: egui::Key, modifiers: egui::Modifiers, action: KeyAction, text: Option<&str>) -> bool {
+        let modes = self.term.key_modes();
+        let bytes = if modes.kitty_flags != 0 {
+            keys::encode_kitty(key, modifiers, action, text, modes)
+        } else if action != KeyAction::Release {
+            // egui does not say which keys come from the keypad
+            keys::encode_key(key, modifiers, false, modes)
+        } else {
+            None
+        };
+        let Some(bytes) = bytes else {
+            return false;
+        };
+        if action != KeyAction::Release {
+            self.scroll_offset = 0;
+        }
+        self.send_to_pty(&bytes);
+        true
+    }
+
+    fn paste(&mut self, text: &str) {
+        self.scroll_offset = 0;
+        self.send_to_pty(&paste::encode_paste(text, self.term.bracketed_paste));
+    }
+
+    fn paste_from_clipboard(&mut self) {
+        if let Some(text) = clipboard_text() {
+            self.paste(&text);
+        }
+    }
+
+    // The URL or file reference shown at viewport cell (`col`, `row`).
+    // Relative paths are taken from the foreground job's directory.
+    fn detect_link(&self, col: usize, row: usize) -> Option<(Target, Spans)> {
+        let cwd = pty::foreground_cwd(self.master_fd).or_else(|| std::env::current_dir().ok())?;
+        links::detect_at(&self.term, self.scroll_offset, row, col, &cwd)
+    }
+
+    // Scroll so the previous (or next) prompt marked by shell integration is
+    // the top row. False, leaving the key to the application, on the
+    // alternate screen or when there is no such prompt.
+    fn jump_to_prompt(&mut self, back: bool) -> bool {
+        if self.term.alt_screen() {
+            return false;
+        }
+        let grid = &self.term.grid;
+        let top = grid.line_number(self.scroll_offset, 0);
+        let mut prompts = grid.marks().filter(|(_, m)| m.kind == MarkKind::Prompt).map(|(line, _)| line);
+        let line = if back {
+            prompts.filter(|&line| line < top).last()
+        } else {
+            prompts.find(|&line| line > top)
+        };
+        let Some(line) = line else {
+            return false;
+        };
+        let history = grid.scrollback.len();
+        self.scroll_offset = (grid.scrollback.total() - line.min(grid.scrollback.total())).min(history);
+        true
+    }
+
+    fn scroll_view(&mut self, lines: isize) {
+        let max = self.term.grid.scrollback.len() as isize;
+        self.scroll_offset = (self.scroll_offset as isize + lines).clamp(0, max) as usize;
+    }
+
+    // Pointer input over the terminal at `rect`. Reported to the application
+    // when it enabled mouse tracking; otherwise, or while Shift is held, the
+    // mouse stays local: dragging selects, the wheel scrolls through history
+    // and middle-click pastes.
+    fn handle_mouse(&mut self, input: &egui::InputState, rect: egui::Rect, cell: egui::Vec2) {
+        let modes = self.term.mouse_modes;
+        let report = modes.tracking != MouseTracking::Off && !input.modifiers.shift;
+        let (rows, cols) = (self.term.grid.rows(), self.term.grid.cols());
+        let cell_at = |pos: egui::Pos2| {
+            let p = (pos - rect.min) / cell;
+            ((p.x.max(0.0) as usize).min(cols - 1), (p.y.max(0.0) as usize).min(rows - 1))
+        };
+
+        let top_line = self.term.grid.line_number(self.scroll_offset, 0);
+        let point_at = |pos: egui::Pos2| {
+            let (col, row) = cell_at(pos);
+            Point { line: top_line + row, col }
+        };
+        let hover = input.pointer.hover_pos().filter(|pos| rect.contains(*pos));
+        self.hovered_link = hover.map_or(0, |pos| links::link_at(&self.term, point_at(pos)));
+        // only looked for with Ctrl held, as it means reading the text and
+        // checking the filesystem
+        self.hovered_target = match hover {
+            Some(pos) if input.modifiers.ctrl && self.hovered_link == 0 => {
+                let (col, row) = cell_at(pos);
+                self.detect_link(col, row)
+            }
+            _ => None,
+        };
+
+        let mut events = Vec::new();
+        if report {
+            for ev in &input.events {
+                match *ev {
+                    egui::Event::PointerButton { pos, button, pressed, .. } => {
+                        let button = match button {
+                            egui::PointerButton::Primary => MouseButton::Left,
+                            egui::PointerButton::Middle => MouseButton::Middle,
+                            egui::PointerButton::Secondary => MouseButton::Right,
+                            _ => continue,
+                        };
+                        if pressed && rect.contains(pos) {
+                            self.mouse_held = Some(button);
+                            self.mouse_cell = Some(cell_at(pos));
+                            events.push((MouseEvent::Press(button), cell_at(pos)));
+                        } else if !pressed && self.mouse_held == Some(button) {
+                            // released wherever the drag ended, even outside
+                            self.mouse_held = None;
+                            events.push((MouseEvent::Release(button), cell_at(pos)));
+                        }
+                    }
+                    egui::Event::PointerMoved(pos) if rect.contains(pos) || self.mouse_held.is_some() => {
+                        let at = cell_at(pos);
+                        if self.mouse_cell != Some(at) {
+                            self.mouse_cell = Some(at);
+                            events.push((MouseEvent::Motion(self.mouse_held), at));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            for ev in &input.events {
+                match *ev {
+                    egui::Event::PointerButton {
+                        pos,
+                        button: egui::PointerButton::Primary,
+                        pressed: true,
+                        modifiers,
+                    } if rect.contains(pos) => {
+                        let at = point_at(pos);
+                        // Ctrl-click opens links, explicit ones first
+                        if modifiers.ctrl {
+                            if links::open_link_at(&self.term, at, self.opener.as_ref()) {
+                                continue;
+                            }
+                            let (col, row) = cell_at(pos);
+                            if let Some((target, _)) = self.detect_link(col, row) {
+                                links::open_target(&target, self.editor.as_deref(), self.opener.as_ref());
+                                continue;
+                            }
+                        }
+                        let clicks = match self.last_click {
+                            Some((time, last, n)) if last == at && input.time - time < DOUBLE_CLICK_SECS => n % 3 + 1,
+                            _ => 1,
+                        };
+                        self.last_click = Some((input.time, at, clicks));
+                        self.selecting = Some(at);
+                        self.selection = match (clicks, self.selection) {
+                            (1, Some(mut selection)) if modifiers.shift => {
+                                selection.extend(at);
+                                Some(selection)
+                            }
+                            // a plain click only starts a drag
+                            (1, _) => None,
+                            (2, _) => Some(Selection::new(SelectionKind::Word, at)),
+                            _ => Some(Selection::new(SelectionKind::Line, at)),
+                        };
+                    }
+                    egui::Event::PointerButton {
+                        button: egui::PointerButton::Primary,
+                        pressed: false,
+                        ..
+                    } => self.selecting = None,
+                    egui::Event::PointerMoved(pos) => {
+                        let Some(anchor) = self.selecting else {
+                            continue;
+                        };
+                        let at = point_at(pos);
+                        match &mut self.selection {
+                            Some(selection) => selection.extend(at),
+                            None if at != anchor => {
+                                let kind = if input.modifiers.alt { SelectionKind::Block } else { SelectionKind::Simple };
+                                let mut selection = Selection::new(kind, anchor);
+                                selection.extend(at);
+                                self.selection = Some(selection);
+                            }
+                            None => {}
+                        }
+                    }
+                    egui::Event::PointerButton {
+                        pos,
+                        button: egui::PointerButton::Middle,
+                        pressed: true,
+                        ..
+                    } if rect.contains(pos) => self.paste_from_clipboard(),
+                    _ => {}
+                }
+            }
+        }
+
+        // Three lines per wheel notch
+        let wheel = input.raw_scroll_delta.y;
+        if wheel != 0.0 {
+            let notches = (wheel.abs() / 50.0).round().max(1.0).copysign(wheel) as isize;
+            match input.pointer.hover_pos().filter(|pos| rect.contains(*pos)) {
+                Some(pos) if report => {
+                    let button = if notches > 0 { MouseButton::WheelUp } else { MouseButton::WheelDown };
+                    for _ in 0..notches.abs() {
+                        events.push((MouseEvent::Press(button), cell_at(pos)));
+                    }
+                }
+                _ => self.scroll_view(notches * 3),
+            }
+        }
+
+        for (event, (col, row)) in events {
+            if let Some(bytes) = mouse::encode_mouse(modes, event, input.modifiers, col, row) {
+                self.send_to_pty(&bytes);
+            }
+        }
+    }
+
+    // Fit the grid (and the PTY) to the space the central panel offers.
+    fn resize_to(&mut self, available: egui::Vec2, cell: egui::Vec2, pixels_per_point: f32) {
+        let cols = (available.x / cell.x).floor().max(1.0) as usize;
+        let rows = (available.y / cell.y).floor().max(1.0) as usize;
+        if (rows, cols) == (self.term.grid.rows(), self.term.grid.cols()) {
+            return;
+        }
+        self.term.resize(rows, cols);
+        // reflow moves text to other lines
+        self.selection = None;
+        self.selecting = None;
+        self.scroll_offset = self.scroll_offset.min(self.term.grid.scrollback.len());
+        let px_w = cols as f32 * cell.x * pixels_per_point;
+        let px_h = rows as f32 * cell.y * pixels_per_point;
+        pty::set_pty_size(self.master_fd, rows, cols, px_w, px_h);
+    }
 }
 
 impl eframe::App for RetermApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.pump_rx();
+        self.pump_rx(ctx);
+
+        if let Some(status) = self.exit_status {
+            let close = match self.on_exit {
+                OnExit::Hold => false,
+                OnExit::Close => true,
+                OnExit::CloseOnSuccess => status.success(),
+            };
+            if close {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }
+
+        let title = self.term.window_title().unwrap_or(APP_NAME);
+        if title != self.window_title {
+            self.window_title = title.to_owned();
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(self.window_title.clone()));
+        }
 
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
-            ui.label("reterm-of-the-king — MVP");
-            ui.label("Type here; Ctrl-C, etc. ANSI not parsed yet (raw text).");
+            ui.horizontal(|ui| {
+                ui.label(&self.window_title);
+                if let Some(status) = self.exit_status {
+                    ui.label(format!("shell {status}"));
+                } else if let Some(code) = self.term.last_command_status().filter(|&code| code != 0) {
+                    ui.label(format!("last command exited with status {code}"));
+                }
+                let before = self.theme;
+                egui::ComboBox::from_id_source("theme")
+                    .selected_text(&self.themes[self.theme].name)
+                    .show_ui(ui, |ui| {
+                        for (i, theme) in self.themes.iter().enumerate() {
+                            ui.selectable_value(&mut self.theme, i, &theme.name);
+                        }
+                    });
+                if self.theme != before {
+                    self.apply_theme(ctx);
+                }
+            });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let style = ui.style_mut();
-            style.text_styles.insert(TextStyle::Body, egui::FontId::monospace(14.0));
-
+        let panel = egui::Frame::default().fill(self.themes[self.theme].background);
+        egui::CentralPanel::default().frame(panel).show(ctx, |ui| {
             // Capture text input this frame
             let input = ui.input(|i| i.clone());
-            for ev in &input.events {
+            let mut events = input.events.iter().peekable();
+            while let Some(ev) = events.next() {
                 match ev {
                     egui::Event::Text(t) => {
-                        // Unicode text input
+                        // Unicode text input; Alt sends it ESC-prefixed
+                        self.scroll_offset = 0;
+                        if input.modifiers.alt {
+                            self.send_to_pty(b"\x1b");
+                        }
                         self.send_to_pty(t.as_bytes());
                     }
+                    // egui turns Ctrl-C/X/V into clipboard events; in a
+                    // terminal they are plain control characters.
+                    // Ctrl-Shift-C copies the selection
+                    egui::Event::Copy if input.modifiers.shift => {
+                        if let Some(selection) = &self.selection {
+                            ctx.copy_text(selection.text(&self.term.grid));
+                        }
+                    }
+                    egui::Event::Copy => {
+                        self.send_key(egui::Key::C, input.modifiers, KeyAction::Press, None);
+                    }
+                    egui::Event::Cut => {
+                        self.send_key(egui::Key::X, input.modifiers, KeyAction::Press, None);
+                    }
+                    // Ctrl-Shift-V pastes
+                    egui::Event::Paste(text) if input.modifiers.shift => self.paste(text),
+                    egui::Event::Paste(_) => {
+                        self.send_key(egui::Key::V, input.modifiers, KeyAction::Press, None);
+                    }
                     egui::Event::Key {
-                        key,
+                        key: key @ (egui::Key::PageUp | egui::Key::PageDown),
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.shift => {
+                        // Shift+PageUp/PageDown page through the scrollback
+                        let page = self.term.grid.rows() as isize;
+                        let dir = if *key == egui::Key::PageUp { 1 } else { -1 };
+                        self.scroll_view(dir * page);
+                    }
+                    egui::Event::Key {
+                        key: key @ (egui::Key::ArrowUp | egui::Key::ArrowDown),
                         pressed: true,
                         modifiers,
                         ..
+                    } if modifiers.ctrl && modifiers.shift && self.jump_to_prompt(*key == egui::Key::ArrowUp) => {
+                        // Ctrl+Shift+Up/Down jump between shell prompts
+                    }
+                    egui::Event::Key {
+                        key,
+                        pressed,
+                        repeat,
+                        modifiers,
+                        ..
                     } => {
-                        // Basic keys → bytes
-                        use egui::Key::*;
-                        match key {
-                            Enter => self.send_to_pty(b"\n"),
-                            Tab => self.send_to_pty(b"\t"),
-                            Backspace => self.send_to_pty(&[0x7f]), // DEL
-                            ArrowLeft => self.send_to_pty(&[0x1b, b'[', b'D']),
-                            ArrowRight => self.send_to_pty(&[0x1b, b'[', b'C']),
-                            ArrowUp => self.send_to_pty(&[0x1b, b'[', b'A']),
-                            ArrowDown => self.send_to_pty(&[0x1b, b'[', b'B']),
-                            C if modifiers.command || modifiers.ctrl => self.send_to_pty(&[0x03]), // Ctrl-C
-                            D if modifiers.command || modifiers.ctrl => self.send_to_pty(&[0x04]), // Ctrl-D
-                            L if modifiers.command || modifiers.ctrl => self.send_to_pty(&[0x0c]), // Ctrl-L
-                            _ => {}
+                        let action = match (pressed, repeat) {
+                            (false, _) => KeyAction::Release,
+                            (true, false) => KeyAction::Press,
+                            (true, true) => KeyAction::Repeat,
+                        };
+                        // egui follows a key press with the text it typed;
+                        // once the key is encoded that text must not be sent too.
+                        let text = match events.peek() {
+                            Some(egui::Event::Text(t)) if *pressed => Some(t.as_str()),
+                            _ => None,
+                        };
+                        if self.send_key(*key, *modifiers, action, text) && text.is_some() {
+                            events.next();
                         }
                     }
                     _ => {}
                 }
             }
 
-            // Text viewport with scroll
-            egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
-                ui.add(
-                    egui::TextEdit::multiline(&mut self.buffer)
-                        .font(TextStyle::Body) // use monospace set above
-                        .desired_width(f32::INFINITY)
-                        .desired_rows(40)
-                        .interactive(false),
-                );
-            });
+            let font = egui::FontId::monospace(FONT_SIZE);
+            let cell = ui.fonts(|f| egui::vec2(f.glyph_width(&font, 'M'), f.row_height(&font)));
+            self.resize_to(ui.available_size(), cell, ctx.pixels_per_point());
+            let grid = &self.term.grid;
+            let size = egui::vec2(cell.x * grid.cols() as f32, cell.y * grid.rows() as f32);
+            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+            self.handle_mouse(&input, rect, cell);
+            let theme = &self.themes[self.theme];
+            let selection = self.selection.as_ref();
+            self.renderer.paint(ui, rect, &self.term, self.scroll_offset, selection, theme);
+            if let Some((_, spans)) = &self.hovered_target {
+                self.renderer.paint_underlines(ui, rect, &self.term, spans, theme);
+            } else {
+                let spans = links::link_spans(&self.term, self.scroll_offset, self.hovered_link);
+                self.renderer.paint_underlines(ui, rect, &self.term, &spans, theme);
+            }
+            if (self.hovered_link != 0 || self.hovered_target.is_some()) && input.modifiers.ctrl {
+                ctx.set_cursor_icon(egui::CursorIcon::PointingHand);
+            }
         });
-
-        ctx.request_repaint(); // simple: repaint every frame
     }
 }
 
 fn main() -> eframe::Result<()> {
+    let config = Config::load();
+
     // 1) PTY + shell
-    let master_fd = spawn_shell_pty();
+    let pty = pty::spawn_shell_pty(config.shell.integration);
+    let master_fd = pty.master;
 
-    // 2) Start background reader from PTY
-    let (tx, rx) = unbounded::<Vec<u8>>();
-    start_reader_thread(master_fd, tx);
 
-    // 3) Launch our own window
+    // 2) Launch our own window
     let native_opts = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([900.0, 600.0]),
@@ -165,8 +579,18 @@ fn main() -> eframe::Result<()> {
 
 
    eframe::run_native(
-        "reterm-of-the-king",
+        APP_NAME,
         native_opts,
-        Box::new(move |_cc| Box::new(RetermApp::new(master_fd, rx))),
+        Box::new(move |cc| {
+            // 3) Start background reader from PTY, and watch for the shell
+            // exiting. Both wake the UI when they have news.
+            let (tx, rx) = unbounded::<PtyEvent>();
+            pty::start_reader_thread(master_fd, tx.clone(), cc.egui_ctx.clone());
+            pty::start_child_watcher(pty.child, tx, cc.egui_ctx.clone());
+
+            let app = RetermApp::new(master_fd, rx, &config);
+            app.apply_theme(&cc.egui_ctx);
+            Box::new(app)
+        }),
     )
 }
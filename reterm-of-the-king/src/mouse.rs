@@ -0,0 +1,167 @@
+// Mouse events → bytes for the PTY, for applications that turned on xterm
+// mouse tracking.
+
+use eframe::egui::Modifiers;
+
+// Which events get reported (DECSET 9/1000/1002/1003).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    // presses only, without modifiers
+    X10,
+    // presses and releases
+    Normal,
+    // ... plus motion while a button is held
+    ButtonEvent,
+    // ... plus all motion
+    AnyEvent,
+}
+
+// How reports are encoded (DECSET 1006/1015, or xterm's original bytes).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MouseEncoding {
+    #[default]
+    Default,
+    Sgr,
+    Urxvt,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseModes {
+    pub tracking: MouseTracking,
+    pub encoding: MouseEncoding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseEvent {
+    Press(MouseButton),
+    Release(MouseButton),
+    // the pointer entered another cell, with the button held (if any)
+    Motion(Option<MouseButton>),
+}
+
+// The report for `event` at the zero-based cell (`col`, `row`), or None when
+// the tracking mode doesn't ask for it or the position can't be encoded.
+pub fn encode_mouse(modes: MouseModes, event: MouseEvent, mods: Modifiers, col: usize, row: usize) -> Option<Vec<u8>> {
+    match (modes.tracking, event) {
+        (MouseTracking::Off, _)
+        | (MouseTracking::X10, MouseEvent::Release(_) | MouseEvent::Motion(_))
+        | (MouseTracking::Normal, MouseEvent::Motion(_))
+        | (MouseTracking::ButtonEvent, MouseEvent::Motion(None)) => return None,
+        _ => {}
+    }
+
+    let (button, release, motion) = match event {
+        MouseEvent::Press(b) => (Some(b), false, false),
+        MouseEvent::Release(b) => (Some(b), true, false),
+        MouseEvent::Motion(b) => (b, false, true),
+    };
+    let mut cb = match button {
+        // only SGR says which button was released
+        _ if release && modes.encoding != MouseEncoding::Sgr => 3,
+        Some(MouseButton::Left) => 0,
+        Some(MouseButton::Middle) => 1,
+        Some(MouseButton::Right) => 2,
+        Some(MouseButton::WheelUp) => 64,
+        Some(MouseButton::WheelDown) => 65,
+        None => 3,
+    };
+    if motion {
+        cb += 32;
+    }
+    if modes.tracking != MouseTracking::X10 {
+        cb += 4 * mods.shift as usize + 8 * mods.alt as usize + 16 * mods.ctrl as usize;
+    }
+
+    let (x, y) = (col + 1, row + 1);
+    Some(match modes.encoding {
+        MouseEncoding::Sgr => {
+            let last = if release { 'm' } else { 'M' };
+            format!("\x1b[<{cb};{x};{y}{last}").into_bytes()
+        }
+        MouseEncoding::Urxvt => format!("\x1b[{};{x};{y}M", cb + 32).into_bytes(),
+        // one byte per value, offset by 32, so positions past 223 are lost
+        MouseEncoding::Default => {
+            if x > 223 || y > 223 {
+                return None;
+            }
+            vec![0x1b, b'[', b'M', (cb + 32) as u8, (x + 32) as u8, (y + 32) as u8]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use MouseButton::*;
+    use MouseEvent::*;
+
+    const NONE: Modifiers = Modifiers::NONE;
+
+    fn modes(tracking: MouseTracking, encoding: MouseEncoding) -> MouseModes {
+        MouseModes { tracking, encoding }
+    }
+
+    #[test]
+    fn encodings_table() {
+        let normal = |encoding| modes(MouseTracking::Normal, encoding);
+        let table: &[(MouseModes, MouseEvent, Modifiers, &[u8])] = &[
+            (normal(MouseEncoding::Default), Press(Left), NONE, b"\x1b[M !!"),
+            (normal(MouseEncoding::Default), Press(Right), NONE, b"\x1b[M\"!!"),
+            (normal(MouseEncoding::Default), Release(Right), NONE, b"\x1b[M#!!"),
+            (normal(MouseEncoding::Default), Press(WheelUp), NONE, b"\x1b[M`!!"),
+            (normal(MouseEncoding::Default), Press(Left), Modifiers::CTRL, b"\x1b[M0!!"),
+            (normal(MouseEncoding::Sgr), Press(Left), NONE, b"\x1b[<0;1;1M"),
+            (normal(MouseEncoding::Sgr), Release(Middle), NONE, b"\x1b[<1;1;1m"),
+            (normal(MouseEncoding::Sgr), Press(WheelDown), Modifiers::ALT, b"\x1b[<73;1;1M"),
+            (normal(MouseEncoding::Urxvt), Press(Left), NONE, b"\x1b[32;1;1M"),
+            (normal(MouseEncoding::Urxvt), Release(Left), NONE, b"\x1b[35;1;1M"),
+        ];
+        for (m, event, mods, expected) in table {
+            let got = encode_mouse(*m, *event, *mods, 0, 0);
+            assert_eq!(got.as_deref(), Some(*expected), "{m:?} {event:?}");
+        }
+    }
+
+    #[test]
+    fn tracking_modes_filter_events() {
+        let sgr = |tracking| modes(tracking, MouseEncoding::Sgr);
+        let table: &[(MouseTracking, MouseEvent, Option<&[u8]>)] = &[
+            (MouseTracking::Off, Press(Left), None),
+            (MouseTracking::X10, Press(Left), Some(b"\x1b[<0;3;2M")),
+            (MouseTracking::X10, Release(Left), None),
+            (MouseTracking::Normal, Motion(Some(Left)), None),
+            (MouseTracking::ButtonEvent, Motion(Some(Left)), Some(b"\x1b[<32;3;2M")),
+            (MouseTracking::ButtonEvent, Motion(None), None),
+            (MouseTracking::AnyEvent, Motion(None), Some(b"\x1b[<35;3;2M")),
+        ];
+        for (tracking, event, expected) in table {
+            let got = encode_mouse(sgr(*tracking), *event, NONE, 2, 1);
+            assert_eq!(got.as_deref(), *expected, "{tracking:?} {event:?}");
+        }
+        // X10 mode never reports modifiers
+        let got = encode_mouse(sgr(MouseTracking::X10), Press(Left), Modifiers::CTRL, 0, 0);
+        assert_eq!(got.as_deref(), Some(&b"\x1b[<0;1;1M"[..]));
+    }
+
+    #[test]
+    fn default_encoding_drops_far_positions() {
+        let m = modes(MouseTracking::Normal, MouseEncoding::Default);
+        assert_eq!(encode_mouse(m, Press(Left), NONE, 223, 0), None);
+        assert!(encode_mouse(m, Press(Left), NONE, 222, 0).is_some());
+        let sgr = modes(MouseTracking::Normal, MouseEncoding::Sgr);
+        let got = encode_mouse(sgr, Press(Left), NONE, 299, 0);
+        assert_eq!(got.as_deref(), Some(&b"\x1b[<0;300;1M"[..]));
+    }
+}
@@ -0,0 +1,83 @@
+// Clipboard text → bytes for the PTY.
+
+// Skip the rest of a CSI sequence: parameters and intermediates, then the
+// final byte.
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| ('\x20'..='\x3f').contains(c)).is_some() {}
+    chars.next_if(|c| ('\x40'..='\x7e').contains(c));
+}
+
+// Skip the rest of an OSC/DCS/APC/PM/SOS string, up to BEL or ST.
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => return,
+            '\x1b' if chars.next_if_eq(&'\\').is_some() => return,
+            _ => {}
+        }
+    }
+}
+
+// Pasted text as the application should see it: wrapped in
+// `ESC[200~ ... ESC[201~` when it enabled bracketed paste, with line breaks
+// sent as CR like typed ones. Escape sequences and other control characters
+// are dropped, so pasted text can neither end the bracket early nor drive
+// the application.
+pub fn encode_paste(text: &str, bracketed: bool) -> Vec<u8> {
+    let mut out = String::with_capacity(text.len() + 12);
+    if bracketed {
+        out.push_str("\x1b[200~");
+    }
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                out.push('\r');
+            }
+            '\n' => out.push('\r'),
+            '\t' => out.push('\t'),
+            '\x1b' if chars.next_if_eq(&'[').is_some() => skip_csi(&mut chars),
+            '\u{9b}' => skip_csi(&mut chars),
+            '\x1b' if chars.next_if(|c| "]P_^X".contains(*c)).is_some() => skip_string(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}'..='\u{9f}' => skip_string(&mut chars),
+            // ESC and the character after it
+            '\x1b' => {
+                chars.next();
+            }
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    if bracketed {
+        out.push_str("\x1b[201~");
+    }
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paste_table() {
+        let table: &[(&str, bool, &[u8])] = &[
+            ("ls -l", false, b"ls -l"),
+            ("ls -l", true, b"\x1b[200~ls -l\x1b[201~"),
+            ("a\nb\r\nc\rd", false, b"a\rb\rc\rd"),
+            ("tab\there", false, b"tab\there"),
+            ("héllo", false, "héllo".as_bytes()),
+            // a paste can't close the bracket and run what follows
+            ("x\x1b[201~rm -rf ~\n", true, b"\x1b[200~xrm -rf ~\r\x1b[201~"),
+            ("\u{9b}201~y", true, b"\x1b[200~y\x1b[201~"),
+            ("\x1b[31mred\x1b[0m", false, b"red"),
+            ("\x1b]0;title\x07x", false, b"x"),
+            ("\x1b]52;c;aGk=\x1b\\x", false, b"x"),
+            ("\x1bcreset", false, b"reset"),
+            ("bell\x07\x00\x7f", false, b"bell"),
+        ];
+        for (text, bracketed, expected) in table {
+            assert_eq!(encode_paste(text, *bracketed), *expected, "{text:?}");
+        }
+    }
+}
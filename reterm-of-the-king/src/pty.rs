@@ -0,0 +1,191 @@
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+    os::unix::io::RawFd,
+    path::PathBuf,
+    thread,
+};
+
+use crossbeam_channel::Sender;
+use eframe::egui;
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    pty::Winsize,
+    sys::wait::{waitpid, WaitPidFlag, WaitStatus},
+    unistd::{execvp, read, ForkResult, Pid},
+};
+use signal_hook::{consts::SIGCHLD, iterator::Signals};
+
+use crate::integration;
+
+// What the background threads report to the UI.
+pub enum PtyEvent {
+    Output(Vec<u8>),
+    // The slave side is gone (EOF/EIO) or reading failed for good; the
+    // reader thread has stopped.
+    Closed,
+    Exited(ExitStatus),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    Code(i32),
+    Signal(nix::sys::signal::Signal),
+}
+
+impl ExitStatus {
+    pub fn success(self) -> bool {
+        self == ExitStatus::Code(0)
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitStatus::Code(code) => write!(f, "exited with status {code}"),
+            ExitStatus::Signal(sig) => write!(f, "killed by {sig:?}"),
+        }
+    }
+}
+
+pub struct Pty {
+    pub master: RawFd,
+    pub child: Pid,
+}
+
+// Start $SHELL on a new PTY, with shell integration loaded if asked and
+// the shell has one.
+pub fn spawn_shell_pty(integration: bool) -> Pty {
+    let shell = std::env::var("SHELL").unwrap_or("/bin/bash".to_owned());
+    let launch = match integration::scripts_dir() {
+        Some(dir) if integration => integration::prepare(&shell, &dir).unwrap_or_else(|e| {
+            eprintln!("shell integration: {e}");
+            None
+        }),
+        _ => None,
+    };
+    let launch = launch.unwrap_or_default();
+    // built before forking, so the child only has to exec
+    let argv: Vec<CString> = std::iter::once(&shell)
+        .chain(&launch.args)
+        .map(|arg| CString::new(arg.as_str()).unwrap())
+        .collect();
+    let argv: Vec<&CStr> = argv.iter().map(CString::as_c_str).collect();
+
+    // (Optional) set initial window size for the PTY so apps see a sane rows/cols.
+    let ws = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    match nix::pty::forkpty(&ws, None) {
+        Ok(fork_pty_res) => {
+            let master = fork_pty_res.master;
+            match fork_pty_res.fork_result {
+                ForkResult::Child => {
+                    // In child: replace with the shell, inheriting the slave as stdio.
+                    for (key, value) in &launch.env {
+                        // the child has no other threads to race with
+                        unsafe { std::env::set_var(key, value) };
+                    }
+                    match execvp(argv[0], &argv) {
+                        Err(e) => panic!("execvp(shell) failed: {e:?}"),
+                    }
+                }
+                ForkResult::Parent { child } => Pty { master, child },
+            }
+        }
+        Err(e) => panic!("forkpty failed: {e:?}"),
+    }
+}
+
+// Tell the kernel (and through SIGWINCH, the foreground job) the new size.
+pub fn set_pty_size(master_fd: RawFd, rows: usize, cols: usize, px_w: f32, px_h: f32) {
+    let ws = Winsize {
+        ws_row: rows as u16,
+        ws_col: cols as u16,
+        ws_xpixel: px_w as u16,
+        ws_ypixel: px_h as u16,
+    };
+    unsafe {
+        libc::ioctl(master_fd, libc::TIOCSWINSZ, &ws);
+    }
+}
+
+// Working directory of the job in the foreground, so relative paths in its
+// output resolve where it meant them.
+pub fn foreground_cwd(master_fd: RawFd) -> Option<PathBuf> {
+    let pgid = unsafe { libc::tcgetpgrp(master_fd) };
+    if pgid <= 0 {
+        return None;
+    }
+    std::fs::read_link(format!("/proc/{pgid}/cwd")).ok()
+}
+
+// Events wake the UI through `ctx`, so it only repaints when something
+// actually happened.
+pub fn start_reader_thread(master_fd: RawFd, tx: Sender<PtyEvent>, ctx: egui::Context) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            match read(master_fd, &mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if tx.send(PtyEvent::Output(buf[..n].to_vec())).is_err() {
+                        // UI is gone, nobody to read for
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                Err(nix::Error::Sys(Errno::EAGAIN)) => {
+                    // Block until there is something to read instead of spinning.
+                    let mut fds = [PollFd::new(master_fd, PollFlags::POLLIN)];
+                    match poll(&mut fds, -1) {
+                        Ok(_) | Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                        Err(e) => {
+                            eprintln!("pty: poll failed: {e}");
+                            break;
+                        }
+                    }
+                }
+                // Linux reports EIO once every slave fd is closed, i.e. the
+                // shell and its children have exited.
+                Err(nix::Error::Sys(Errno::EIO)) => break,
+                Err(e) => {
+                    eprintln!("pty: read failed: {e}");
+                    break;
+                }
+            }
+        }
+        let _ = tx.send(PtyEvent::Closed);
+        ctx.request_repaint();
+    });
+}
+
+// Reap the shell on SIGCHLD and report how it ended.
+pub fn start_child_watcher(child: Pid, tx: Sender<PtyEvent>, ctx: egui::Context) {
+    let mut signals = Signals::new([SIGCHLD]).expect("registering SIGCHLD handler");
+    thread::spawn(move || {
+        loop {
+            // Check before waiting too: the child may already be gone.
+            let status = match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::Exited(_, code)) => Some(ExitStatus::Code(code)),
+                Ok(WaitStatus::Signaled(_, sig, _)) => Some(ExitStatus::Signal(sig)),
+                Ok(_) => None,
+                // ECHILD: somebody else reaped it; nothing more to learn
+                Err(_) => break,
+            };
+            if let Some(status) = status {
+                let _ = tx.send(PtyEvent::Exited(status));
+                ctx.request_repaint();
+                break;
+            }
+            if signals.wait().next().is_none() {
+                break;
+            }
+        }
+    });
+}
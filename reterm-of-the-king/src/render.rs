@@ -0,0 +1,270 @@
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+use eframe::egui::{
+    self,
+    text::{LayoutJob, TextFormat},
+    Color32, FontId, Painter, Pos2, Rect, Shape, Stroke, Vec2,
+};
+
+use crate::{
+    grid::{Cell, Color, Flags, Row, Underline},
+    selection::Selection,
+    term::Term,
+    theme::Theme,
+};
+
+// Blend `a` towards `b`; used for SGR 2 (dim).
+fn mix(a: Color32, b: Color32, t: f32) -> Color32 {
+    let m = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    Color32::from_rgb(m(a.r(), b.r()), m(a.g(), b.g()), m(a.b(), b.b()))
+}
+
+// Foreground and background of a cell after applying its attributes.
+fn cell_colors(cell: &Cell, theme: &Theme, blink_off: bool, selected: bool) -> (Color32, Color32) {
+    let bold = cell.flags.contains(Flags::BOLD);
+    let mut fg = match cell.fg {
+        // bold brightens the 8 basic colors, as xterm does
+        Color::Indexed(i) if bold && i < 8 => theme.indexed(i + 8),
+        c => theme.resolve(c, theme.foreground),
+    };
+    let mut bg = theme.resolve(cell.bg, theme.background);
+    if cell.flags.contains(Flags::INVERSE) {
+        std::mem::swap(&mut fg, &mut bg);
+    }
+    if selected {
+        bg = theme.selection;
+    }
+    if cell.flags.contains(Flags::DIM) {
+        fg = mix(fg, bg, 0.4);
+    }
+    if cell.flags.contains(Flags::HIDDEN) || (blink_off && cell.flags.contains(Flags::BLINK)) {
+        fg = bg;
+    }
+    (fg, bg)
+}
+
+// Draw an underline of `style` along the bottom of a run `width` wide.
+fn underline_shapes(out: &mut Vec<Shape>, pos: Pos2, width: f32, cell: Vec2, style: Underline, color: Color32) {
+    let stroke = Stroke::new(1.0, color);
+    let y = pos.y + cell.y - 1.5;
+    let (x0, x1) = (pos.x, pos.x + width);
+    // evenly spaced segments `on` long with `off` gaps
+    let mut segments = |on: f32, off: f32| {
+        let mut x = x0;
+        while x < x1 {
+            out.push(Shape::hline(x..=(x + on).min(x1), y, stroke));
+            x += on + off;
+        }
+    };
+    match style {
+        Underline::None => {}
+        Underline::Single => out.push(Shape::hline(x0..=x1, y, stroke)),
+        Underline::Double => {
+            out.push(Shape::hline(x0..=x1, y - 2.0, stroke));
+            out.push(Shape::hline(x0..=x1, y, stroke));
+        }
+        Underline::Curly => {
+            let points = (0..=(width / 1.5) as usize)
+                .map(|i| {
+                    let x = x0 + i as f32 * 1.5;
+                    egui::pos2(x, y - 1.0 + (x * 0.8).sin() * 1.2)
+                })
+                .collect();
+            out.push(Shape::line(points, stroke));
+        }
+        Underline::Dotted => segments(1.0, 1.0),
+        Underline::Dashed => segments(4.0, 2.0),
+    }
+}
+
+// A row laid out at the origin: backgrounds and underlines as shapes, and
+// the text as layout jobs. Galleys are not kept across frames, since egui
+// rebuilds its font atlas on zoom or when it fills up, and its own galley
+// cache already makes laying out an unchanged job cheap.
+#[derive(Clone, Default)]
+struct RowLayout {
+    shapes: Vec<Shape>,
+    // position, job, color and whether to overstrike for bold
+    text: Vec<(Pos2, LayoutJob, Color32, bool)>,
+}
+
+impl RowLayout {
+    fn paint(&self, painter: &Painter, origin: Pos2) {
+        painter.extend(self.shapes.iter().cloned().map(|mut shape| {
+            shape.translate(origin.to_vec2());
+            shape
+        }));
+        for (pos, job, fg, bold) in &self.text {
+            let pos = origin + pos.to_vec2();
+            let galley = painter.layout_job(job.clone());
+            if *bold {
+                // no bold face for the monospace font: overstrike instead
+                painter.galley(pos + egui::vec2(0.5, 0.0), galley.clone(), *fg);
+            }
+            painter.galley(pos, galley, *fg);
+        }
+    }
+}
+
+// Lay out one row, batching runs of cells that share attributes.
+// Backgrounds go first, so a wide character's glyph isn't covered by the
+// background of its spacer. Also returns whether any of it blinks.
+fn row_layout(
+    row: &Row,
+    cell: Vec2,
+    font: &FontId,
+    theme: &Theme,
+    blink_off: bool,
+    selected: Option<Range<usize>>,
+) -> (RowLayout, bool) {
+    let mut layout = RowLayout::default();
+    let mut underlines = Vec::new();
+    let mut blinks = false;
+    let cells = &row.cells;
+    let is_selected = |i: usize| selected.as_ref().is_some_and(|r| r.contains(&i));
+    let mut start = 0;
+    while start < cells.len() {
+        let first = &cells[start];
+        let end = cells[start..]
+            .iter()
+            .enumerate()
+            .position(|(n, c)| !c.same_style(first) || is_selected(start + n) != is_selected(start))
+            .map_or(cells.len(), |n| start + n);
+        let (fg, bg) = cell_colors(first, theme, blink_off, is_selected(start));
+        let pos = egui::pos2(start as f32 * cell.x, 0.0);
+
+        let width = (end - start) as f32 * cell.x;
+        if bg != theme.background {
+            let size = egui::vec2(width, cell.y);
+            layout.shapes.push(Shape::rect_filled(Rect::from_min_size(pos, size), 0.0, bg));
+        }
+
+        let text: String = cells[start..end].iter().flat_map(Cell::text).collect();
+        let flags = first.flags;
+        blinks |= flags.contains(Flags::BLINK);
+        if first.underline != Underline::None && fg != bg {
+            let color = theme.resolve(first.underline_color, fg);
+            underline_shapes(&mut underlines, pos, width, cell, first.underline, color);
+        }
+        if flags.contains(Flags::STRIKE) || !text.trim().is_empty() {
+            let strike = if flags.contains(Flags::STRIKE) { Stroke::new(1.0, fg) } else { Stroke::NONE };
+            let job = LayoutJob::single_section(
+                text,
+                TextFormat {
+                    font_id: font.clone(),
+                    color: fg,
+                    italics: flags.contains(Flags::ITALIC),
+                    strikethrough: strike,
+                    ..Default::default()
+                },
+            );
+            layout.text.push((pos, job, fg, flags.contains(Flags::BOLD)));
+        }
+        start = end;
+    }
+    layout.shapes.append(&mut underlines);
+    (layout, blinks)
+}
+
+fn paint_cursor(painter: &Painter, rect: Rect, term: &Term, cell: Vec2, font: &FontId, theme: &Theme) {
+    let cur = term.grid.cursor;
+    let min = rect.min + egui::vec2(cur.col as f32 * cell.x, cur.row as f32 * cell.y);
+    painter.rect_filled(Rect::from_min_size(min, cell), 0.0, theme.cursor);
+    // redraw the character under the cursor so it stays readable
+    let text: String = term.grid.visible_row(0, cur.row).cells[cur.col].text().collect();
+    painter.text(min, egui::Align2::LEFT_TOP, text, font.clone(), theme.background);
+}
+
+// Paints the viewport row by row, reusing the layout of rows that have not
+// changed since the last frame (see `Row::version`).
+#[derive(Default)]
+pub struct Renderer {
+    rows: HashMap<u64, RowLayout>,
+    // theme and cell size the cached rows were laid out with
+    layout_key: Option<(String, Vec2)>,
+}
+
+impl Renderer {
+    // Paint into `rect`, which the caller sized to fit the grid exactly.
+    pub fn paint(
+        &mut self,
+        ui: &egui::Ui,
+        rect: Rect,
+        term: &Term,
+        scroll_offset: usize,
+        selection: Option<&Selection>,
+        theme: &Theme,
+    ) {
+        let font = FontId::monospace(crate::FONT_SIZE);
+        let grid = &term.grid;
+        let cell = rect.size() / egui::vec2(grid.cols() as f32, grid.rows() as f32);
+        let painter = ui.painter_at(rect);
+
+        let layout_key = Some((theme.name.clone(), cell));
+        if self.layout_key != layout_key {
+            self.rows.clear();
+            self.layout_key = layout_key;
+        }
+
+        // SGR 5 text and the cursor blink at 1Hz
+        let time = ui.input(|i| i.time);
+        let blink_off = (time * 2.0) as u64 % 2 == 1;
+
+        // Whatever is left in `previous` afterwards scrolled out of view and
+        // is dropped.
+        let mut previous = std::mem::take(&mut self.rows);
+        let mut blinks = false;
+        for r in 0..grid.rows() {
+            let row = grid.visible_row(scroll_offset, r);
+            let version = row.version();
+            let selected = selection.and_then(|s| s.columns(grid, grid.line_number(scroll_offset, r)));
+            let origin = rect.min + egui::vec2(0.0, r as f32 * cell.y);
+            if selected.is_some() {
+                // selected rows are laid out fresh and never cached
+                let (layout, row_blinks) = row_layout(row, cell, &font, theme, blink_off, selected);
+                blinks |= row_blinks;
+                layout.paint(&painter, origin);
+            } else if let Some(layout) = self.rows.get(&version) {
+                layout.paint(&painter, origin);
+            } else if let Some(layout) = previous.remove(&version) {
+                layout.paint(&painter, origin);
+                self.rows.insert(version, layout);
+            } else {
+                let (layout, row_blinks) = row_layout(row, cell, &font, theme, blink_off, None);
+                blinks |= row_blinks;
+                layout.paint(&painter, origin);
+                // blinking rows change with the clock, so never reuse them
+                if !row_blinks {
+                    self.rows.insert(version, layout);
+                }
+            }
+        }
+
+        if term.cursor_visible && scroll_offset == 0 {
+            blinks |= term.cursor_blink;
+            if !(term.cursor_blink && blink_off) {
+                paint_cursor(&painter, rect, term, cell, &font, theme);
+            }
+        }
+
+        // Nothing else repaints an idle terminal, so wake up for the next phase.
+        if blinks {
+            let until_toggle = 0.5 - time % 0.5;
+            ui.ctx().request_repaint_after(Duration::from_secs_f64(until_toggle));
+        }
+    }
+    // Underline spans of (viewport row, columns) over the text, e.g. the link
+    // under the pointer. Drawn apart from the cached rows since it follows
+    // the pointer, not the text.
+    pub fn paint_underlines(&self, ui: &egui::Ui, rect: Rect, term: &Term, spans: &[(usize, Range<usize>)], theme: &Theme) {
+        let grid = &term.grid;
+        let cell = rect.size() / egui::vec2(grid.cols() as f32, grid.rows() as f32);
+        let painter = ui.painter_at(rect);
+        let stroke = Stroke::new(1.0, theme.foreground);
+        for (row, cols) in spans {
+            let y = rect.min.y + (*row as f32 + 1.0) * cell.y - 1.5;
+            let x = |col: usize| rect.min.x + col as f32 * cell.x;
+            painter.hline(x(cols.start)..=x(cols.end), y, stroke);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use crate::grid::Row;
+
+// Whole lines that scrolled off the top of the grid, oldest first. Bounded by
+// a line count; once full, each push evicts the oldest line.
+pub struct Scrollback {
+    lines: VecDeque<Row>,
+    limit: usize,
+    // Lines ever pushed, so views can tell how far the history moved.
+    total: usize,
+}
+
+impl Scrollback {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            limit,
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, row: Row) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.lines.len() == self.limit {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(row);
+        self.total += 1;
+    }
+
+    // Take back the newest line, e.g. when the screen grows taller.
+    pub fn pop(&mut self) -> Option<Row> {
+        let row = self.lines.pop_back()?;
+        self.total -= 1;
+        Some(row)
+    }
+
+    // Remove every line, oldest first, e.g. to re-wrap them.
+    pub fn take(&mut self) -> Vec<Row> {
+        self.total -= self.lines.len();
+        self.lines.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    // Line `i`, counting from the oldest line still kept.
+    pub fn get(&self, i: usize) -> Option<&Row> {
+        self.lines.get(i)
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Cell;
+
+    fn row(c: char) -> Row {
+        let mut row = Row::new(1, &Cell::default());
+        row.cells[0].c = c;
+        row
+    }
+
+    fn lines(scrollback: &Scrollback) -> String {
+        (0..scrollback.len()).map(|i| scrollback.get(i).unwrap().cells[0].c).collect()
+    }
+
+    #[test]
+    fn keeps_the_newest_lines() {
+        let mut scrollback = Scrollback::new(3);
+        for c in "abcde".chars() {
+            scrollback.push(row(c));
+        }
+        assert_eq!(lines(&scrollback), "cde");
+        assert_eq!(scrollback.total(), 5);
+        assert_eq!(scrollback.pop().unwrap().cells[0].c, 'e');
+        assert_eq!((lines(&scrollback).as_str(), scrollback.total()), ("cd", 4));
+        assert_eq!(scrollback.take().len(), 2);
+        assert_eq!((scrollback.len(), scrollback.total()), (0, 2));
+
+        let mut none = Scrollback::new(0);
+        none.push(row('a'));
+        assert_eq!((none.len(), none.total()), (0, 0));
+    }
+}
@@ -0,0 +1,225 @@
+// Text selected with the mouse. Positions use absolute line numbers (see
+// `Grid::line_number`), so a selection stays on its text while the view
+// scrolls or output pushes lines into history.
+
+use std::ops::Range;
+
+use crate::grid::{Cell, Flags, Grid, Row};
+
+// Field order makes the derived ordering reading order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionKind {
+    // character by character, from click-drag
+    Simple,
+    // whole words, from a double click
+    Word,
+    // whole lines, from a triple click
+    Line,
+    // a rectangle of columns, from Alt-drag
+    Block,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Selection {
+    kind: SelectionKind,
+    anchor: Point,
+    head: Point,
+}
+
+// Characters that end a word for double-click selection. The right half of
+// a wide character belongs to whatever the left half does.
+fn is_word_char(cell: &Cell) -> bool {
+    let c = cell.c;
+    cell.flags.contains(Flags::SPACER) || (!c.is_whitespace() && !"\"'`()[]{}<>|;,".contains(c))
+}
+
+fn word_start(row: &Row, col: usize) -> usize {
+    let cells = &row.cells[..=col.min(row.cells.len() - 1)];
+    if !cells.last().is_some_and(is_word_char) {
+        return col;
+    }
+    cells.iter().rposition(|c| !is_word_char(c)).map_or(0, |i| i + 1)
+}
+
+fn word_end(row: &Row, col: usize) -> usize {
+    let col = col.min(row.cells.len() - 1);
+    if !is_word_char(&row.cells[col]) {
+        return col;
+    }
+    let rest = &row.cells[col..];
+    rest.iter().position(|c| !is_word_char(c)).map_or(row.cells.len(), |i| col + i) - 1
+}
+
+impl Selection {
+    pub fn new(kind: SelectionKind, at: Point) -> Self {
+        Self { kind, anchor: at, head: at }
+    }
+
+    // Move the end that follows the pointer.
+    pub fn extend(&mut self, to: Point) {
+        self.head = to;
+    }
+
+    // First and last selected cell, in reading order and grown to whole
+    // words or lines.
+    fn bounds(&self, grid: &Grid) -> (Point, Point) {
+        let (mut start, mut end) = (self.anchor.min(self.head), self.anchor.max(self.head));
+        match self.kind {
+            SelectionKind::Simple => {}
+            SelectionKind::Word => {
+                if let Some(row) = grid.line(start.line) {
+                    start.col = word_start(row, start.col);
+                }
+                if let Some(row) = grid.line(end.line) {
+                    end.col = word_end(row, end.col);
+                }
+            }
+            SelectionKind::Line => {
+                start.col = 0;
+                end.col = grid.cols() - 1;
+            }
+            SelectionKind::Block => {
+                start.col = self.anchor.col.min(self.head.col);
+                end.col = self.anchor.col.max(self.head.col);
+            }
+        }
+        (start, end)
+    }
+
+    fn columns_within(&self, (start, end): (Point, Point), line: usize, cols: usize) -> Option<Range<usize>> {
+        if line < start.line || line > end.line {
+            return None;
+        }
+        let range = match self.kind {
+            SelectionKind::Block => start.col..end.col + 1,
+            _ => {
+                let from = if line == start.line { start.col } else { 0 };
+                let to = if line == end.line { end.col + 1 } else { cols };
+                from..to
+            }
+        };
+        Some(range.start.min(cols)..range.end.min(cols))
+    }
+
+    // The selected columns of absolute line `line`, if any.
+    pub fn columns(&self, grid: &Grid, line: usize) -> Option<Range<usize>> {
+        self.columns_within(self.bounds(grid), line, grid.cols())
+    }
+
+    // The selected text, one line per row with trailing blanks trimmed.
+    // Soft-wrapped rows are joined back into the line they came from.
+    pub fn text(&self, grid: &Grid) -> String {
+        let bounds = self.bounds(grid);
+        let (start, end) = bounds;
+        let mut out = String::new();
+        for line in start.line..=end.line {
+            let Some(row) = grid.line(line) else {
+                continue;
+            };
+            let Some(range) = self.columns_within(bounds, line, row.cells.len()) else {
+                continue;
+            };
+            let continues = row.wrapped && range.end == row.cells.len() && self.kind != SelectionKind::Block;
+            let text: String = row.cells[range].iter().flat_map(Cell::text).collect();
+            if continues {
+                out += &text;
+            } else {
+                out += text.trim_end();
+                if line != end.line {
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Term;
+
+    fn term(cols: usize, output: &str) -> Term {
+        let mut term = Term::new(4, cols, 100);
+        term.feed(output.as_bytes());
+        term
+    }
+
+    fn at(line: usize, col: usize) -> Point {
+        Point { line, col }
+    }
+
+    fn select(kind: SelectionKind, from: Point, to: Point) -> Selection {
+        let mut sel = Selection::new(kind, from);
+        sel.extend(to);
+        sel
+    }
+
+    #[test]
+    fn simple_selection_trims_trailing_blanks() {
+        let term = term(10, "hello   \r\nworld");
+        let sel = select(SelectionKind::Simple, at(0, 1), at(1, 9));
+        assert_eq!(sel.text(&term.grid), "ello\nworld");
+        // dragging backwards selects the same cells
+        let sel = select(SelectionKind::Simple, at(1, 9), at(0, 1));
+        assert_eq!(sel.text(&term.grid), "ello\nworld");
+        assert_eq!(sel.columns(&term.grid, 0), Some(1..10));
+        assert_eq!(sel.columns(&term.grid, 1), Some(0..10));
+        assert_eq!(sel.columns(&term.grid, 2), None);
+    }
+
+    #[test]
+    fn soft_wrapped_lines_are_joined() {
+        let term = term(5, "abcdefgh\r\nij");
+        let sel = select(SelectionKind::Simple, at(0, 0), at(2, 4));
+        assert_eq!(sel.text(&term.grid), "abcdefgh\nij");
+    }
+
+    #[test]
+    fn word_and_line_selection() {
+        let term = term(20, "foo bar-baz (qux)\r\nnext line");
+        let word = Selection::new(SelectionKind::Word, at(0, 6));
+        assert_eq!(word.text(&term.grid), "bar-baz");
+        assert_eq!(word.columns(&term.grid, 0), Some(4..11));
+        let word = Selection::new(SelectionKind::Word, at(0, 14));
+        assert_eq!(word.text(&term.grid), "qux");
+        // a double click on a separator selects just that
+        let word = Selection::new(SelectionKind::Word, at(0, 3));
+        assert_eq!(word.text(&term.grid), "");
+        let word = select(SelectionKind::Word, at(0, 1), at(1, 1));
+        assert_eq!(word.text(&term.grid), "foo bar-baz (qux)\nnext");
+
+        let line = Selection::new(SelectionKind::Line, at(1, 3));
+        assert_eq!(line.text(&term.grid), "next line");
+
+        let term = self::term(10, "ab 世界x y");
+        let word = Selection::new(SelectionKind::Word, at(0, 4));
+        assert_eq!(word.text(&term.grid), "世界x");
+    }
+
+    #[test]
+    fn block_selection() {
+        let term = term(10, "abcd\r\nefgh\r\nijkl");
+        let sel = select(SelectionKind::Block, at(2, 1), at(0, 2));
+        assert_eq!(sel.text(&term.grid), "bc\nfg\njk");
+        assert_eq!(sel.columns(&term.grid, 1), Some(1..3));
+    }
+
+    #[test]
+    fn selection_follows_text_into_history() {
+        let mut term = term(10, "one\r\ntwo\r\n");
+        let sel = Selection::new(SelectionKind::Line, at(term.grid.line_number(0, 1), 0));
+        assert_eq!(sel.text(&term.grid), "two");
+        term.feed(b"three\r\nfour\r\nfive\r\n");
+        assert_eq!(sel.text(&term.grid), "two");
+        // the same line, now seen scrolled back into history
+        let line = term.grid.line_number(term.grid.scrollback.len(), 1);
+        assert_eq!(sel.columns(&term.grid, line), Some(0..10));
+    }
+}
@@ -0,0 +1,30 @@
+# reterm-of-the-king shell integration for bash: OSC 133 marks where each
+# prompt, command line and output begins, and how the command exited.
+# Loaded with --rcfile, in place of ~/.bashrc.
+
+if [[ -f ~/.bashrc ]]; then
+    source ~/.bashrc
+fi
+
+__reterm_precmd() {
+    local ret=$?
+    # only after a command ran: not for the first prompt, an empty line or
+    # a line abandoned with Ctrl-C
+    if [[ -n $__reterm_ran ]]; then
+        printf '\e]133;D;%s\a' "$ret"
+        __reterm_ran=
+    fi
+    return $ret
+}
+
+# Runs after the user's PROMPT_COMMAND, which may have rebuilt PS1.
+__reterm_mark_prompt() {
+    if [[ $PS1 != *'133;A'* ]]; then
+        PS1='\[\e]133;A\a\]'$PS1'\[\e]133;B\a\]'
+    fi
+}
+
+PROMPT_COMMAND="__reterm_precmd${PROMPT_COMMAND:+; ${PROMPT_COMMAND%;}}; __reterm_mark_prompt"
+# PS0 is printed once a command line is accepted. The empty substring
+# expansion is there for its side effect, setting __reterm_ran.
+PS0+='\e]133;C\a${PS0:0:(__reterm_ran=1)*0}'
@@ -0,0 +1,21 @@
+# reterm-of-the-king shell integration for fish: OSC 133 marks where each
+# prompt, command line and output begins, and how the command exited.
+# Loaded with --init-command, after the user's configuration.
+
+function __reterm_prompt_start --on-event fish_prompt
+    printf '\e]133;A\a'
+end
+
+function __reterm_preexec --on-event fish_preexec
+    printf '\e]133;C\a'
+end
+
+function __reterm_postexec --on-event fish_postexec
+    printf '\e]133;D;%s\a' $status
+end
+
+functions --copy fish_prompt __reterm_user_prompt
+function fish_prompt
+    __reterm_user_prompt
+    printf '\e]133;B\a'
+end
@@ -0,0 +1,946 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use vte::{Params, Perform};
+
+use crate::{
+    grid::{Cell, Color, Cursor, Flags, Grid, MarkKind, Underline},
+    keys::{KeyModes, KITTY_ALL_FLAGS},
+    mouse::{MouseEncoding, MouseModes, MouseTracking},
+};
+
+// Terminal state driven by the vte parser: owns the screen grid and the
+// modes/attributes that escape sequences change.
+pub struct Term {
+    // Escape-sequence and UTF-8 decoder state. Lives as long as the terminal
+    // so sequences split across PTY reads are carried over to the next one.
+    parser: vte::Parser,
+    // The active screen. While the alternate screen is up, the primary one
+    // (with its scrollback) is parked in `inactive`.
+    pub grid: Grid,
+    inactive: Grid,
+    alt_screen: bool,
+    // Attributes applied to newly printed cells.
+    template: Cell,
+    // DECSC position and attributes. Each screen has its own; the other
+    // screen's is parked in `inactive_saved_cursor`.
+    saved_cursor: (Cursor, Cell),
+    inactive_saved_cursor: (Cursor, Cell),
+    // OSC 2 window title and OSC 1 icon name, with the XTWINOPS 22/23
+    // stacks that save and restore them
+    title: Option<String>,
+    icon_name: Option<String>,
+    title_stack: Vec<Option<String>>,
+    icon_name_stack: Vec<Option<String>>,
+    pub cursor_visible: bool,
+    pub cursor_blink: bool,
+    // DECCKM/DECKPAM, consulted when encoding key presses
+    key_modes: KeyModes,
+    // DECSET 2004: wrap pastes in ESC[200~ ... ESC[201~
+    pub bracketed_paste: bool,
+    // What mouse events the application asked for, and how to encode them
+    pub mouse_modes: MouseModes,
+    // Kitty keyboard flags pushed by the application. Each screen has its
+    // own stack; the other screen's is parked in `inactive_kitty_flags`.
+    kitty_flags: Vec<u8>,
+    inactive_kitty_flags: Vec<u8>,
+    // Replies to queries, waiting to be written back to the PTY
+    responses: Vec<u8>,
+    // OSC 8 hyperlinks; cells refer to them by index plus one. Slots no
+    // cell uses any more are emptied by `sweep_links` and reused.
+    links: Vec<Option<Hyperlink>>,
+    free_links: Vec<usize>,
+    // the slot of each link with an id, so later runs can rejoin it
+    link_ids: HashMap<Hyperlink, usize>,
+    // sweep once `links` has this many slots and none free
+    links_sweep_at: usize,
+    // Requests only the UI can carry out
+    events: Vec<TermEvent>,
+    // OSC 52 copies larger than this many bytes are dropped undecoded
+    pub osc52_max_bytes: usize,
+    // exit status from the last OSC 133;D, if it carried one
+    last_command_status: Option<i32>,
+    scrollback_lines: usize,
+}
+
+// Deepest the kitty keyboard flag stack may get; older entries fall off.
+const KITTY_STACK_LIMIT: usize = 16;
+// Same for the title stacks; xterm keeps 10.
+const TITLE_STACK_LIMIT: usize = 10;
+
+// How many bytes base64 `data` decodes to: 3 for every 4, less padding.
+fn base64_len(data: &[u8]) -> usize {
+    let padding = data.iter().rev().take_while(|&&b| b == b'=').count();
+    (data.len() * 3 / 4).saturating_sub(padding)
+}
+
+fn push_limited<T>(stack: &mut Vec<T>, value: T, limit: usize) {
+    if stack.len() >= limit {
+        stack.remove(0);
+    }
+    stack.push(value);
+}
+
+// Fewest link slots worth sweeping for.
+const LINKS_SWEEP_MIN: usize = 256;
+
+// An OSC 8 hyperlink. Cells that share an explicit `id` (and URI) belong to
+// the same link even when other text separates them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Hyperlink {
+    pub id: Option<String>,
+    pub uri: String,
+}
+
+// Requests from the application that reach outside the terminal.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TermEvent {
+    // OSC 52: put text on the clipboard
+    SetClipboard(String),
+    // OSC 52 with `?`: answer with `clipboard_contents` if policy allows
+    ReadClipboard,
+}
+
+// The n-th parameter, or `default` when it is missing or zero.
+fn arg(params: &Params, n: usize, default: usize) -> usize {
+    match params.iter().nth(n).and_then(|p| p.first().copied()) {
+        Some(0) | None => default,
+        Some(v) => v as usize,
+    }
+}
+
+// Extended color after 38/48: either packed into one colon-separated
+// parameter (`38:2::r:g:b`, `38:5:n`) or spread over the following
+// semicolon-separated ones (`38;2;r;g;b`, `38;5;n`).
+fn extended_color<'a>(param: &[u16], rest: &mut impl Iterator<Item = &'a [u16]>) -> Option<Color> {
+    let mut next = || rest.next().and_then(|p| p.first().copied());
+    let (kind, values) = match param {
+        [_] => {
+            let kind = next()?;
+            let values = match kind {
+                5 => vec![next()?],
+                _ => vec![next()?, next()?, next()?],
+            };
+            (kind, values)
+        }
+        [_, kind, values @ ..] => {
+            // the colon form may carry a color space id before r:g:b
+            let values = match (kind, values.len()) {
+                (2, 4..) => values[1..4].to_vec(),
+                _ => values.to_vec(),
+            };
+            (*kind, values)
+        }
+        [] => return None,
+    };
+    match (kind, values.as_slice()) {
+        (5, [n, ..]) => Some(Color::Indexed(*n as u8)),
+        (2, [r, g, b, ..]) => Some(Color::Rgb(*r as u8, *g as u8, *b as u8)),
+        _ => None,
+    }
+}
+
+impl Term {
+    pub fn new(rows: usize, cols: usize, scrollback_lines: usize) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            grid: Grid::new(rows, cols, scrollback_lines),
+            // the alternate screen never feeds the scrollback
+            inactive: Grid::new(rows, cols, 0),
+            alt_screen: false,
+            template: Cell::default(),
+            saved_cursor: Default::default(),
+            inactive_saved_cursor: Default::default(),
+            title: None,
+            icon_name: None,
+            title_stack: Vec::new(),
+            icon_name_stack: Vec::new(),
+            cursor_visible: true,
+            cursor_blink: true,
+            key_modes: KeyModes::default(),
+            bracketed_paste: false,
+            mouse_modes: MouseModes::default(),
+            kitty_flags: Vec::new(),
+            inactive_kitty_flags: Vec::new(),
+            responses: Vec::new(),
+            links: Vec::new(),
+            free_links: Vec::new(),
+            link_ids: HashMap::new(),
+            links_sweep_at: LINKS_SWEEP_MIN,
+            events: Vec::new(),
+            last_command_status: None,
+            osc52_max_bytes: usize::MAX,
+            scrollback_lines,
+        }
+    }
+
+    // Feed raw PTY output. Chunks may end anywhere, even inside a multibyte
+    // character or an escape sequence.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut parser = std::mem::take(&mut self.parser);
+        for &byte in bytes {
+            parser.advance(self, byte);
+        }
+        self.parser = parser;
+    }
+
+    // Bytes the terminal owes the application (query replies) since the
+    // last call.
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    pub fn take_events(&mut self) -> Vec<TermEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Answer a `TermEvent::ReadClipboard`.
+    pub fn clipboard_contents(&mut self, text: &str) {
+        let reply = format!("\x1b]52;c;{}\x1b\\", BASE64.encode(text));
+        self.responses.extend_from_slice(reply.as_bytes());
+    }
+
+    // The hyperlink that cells with `link` point at.
+    pub fn hyperlink(&self, link: u32) -> Option<&Hyperlink> {
+        self.links.get((link as usize).checked_sub(1)?)?.as_ref()
+    }
+
+    // OSC 8 ; params ; uri: start a link for the cells printed from now on,
+    // or end it with an empty uri.
+    fn set_hyperlink(&mut self, params: &[u8], uri: &[&[u8]]) {
+        // the uri itself may contain semicolons
+        let uri = String::from_utf8_lossy(&uri.join(&b';')).into_owned();
+        if uri.is_empty() {
+            self.template.link = 0;
+            return;
+        }
+        let params = String::from_utf8_lossy(params);
+        let id = params.split(':').find_map(|p| p.strip_prefix("id=")).map(str::to_owned);
+        let link = Hyperlink { id, uri };
+        let index = match self.link_ids.get(&link) {
+            Some(&index) => index,
+            None => self.add_link(link),
+        };
+        self.template.link = index as u32 + 1;
+    }
+
+    fn add_link(&mut self, link: Hyperlink) -> usize {
+        if self.free_links.is_empty() && self.links.len() >= self.links_sweep_at {
+            self.sweep_links();
+        }
+        let index = match self.free_links.pop() {
+            Some(index) => index,
+            None => {
+                self.links.push(None);
+                self.links.len() - 1
+            }
+        };
+        if link.id.is_some() {
+            self.link_ids.insert(link.clone(), index);
+        }
+        self.links[index] = Some(link);
+        index
+    }
+
+    // Free the links that no cell on either screen or in history refers to
+    // any more, and let the table grow to twice what is left before the
+    // next sweep.
+    fn sweep_links(&mut self) {
+        let mut used = vec![false; self.links.len()];
+        let cells = self.grid.all_rows().chain(self.inactive.all_rows()).flat_map(|r| &r.cells);
+        for link in cells.map(|c| c.link).chain([self.template.link]) {
+            if link != 0 {
+                used[link as usize - 1] = true;
+            }
+        }
+        self.free_links.clear();
+        for (index, slot) in self.links.iter_mut().enumerate() {
+            if !used[index]
+                && let Some(link) = slot.take()
+            {
+                self.link_ids.remove(&link);
+            }
+            if slot.is_none() {
+                self.free_links.push(index);
+            }
+        }
+        let live = self.links.len() - self.free_links.len();
+        self.links_sweep_at = (2 * live).max(LINKS_SWEEP_MIN);
+    }
+
+    // Exit status of the last command shell integration saw finish. Kept
+    // apart from the marks so asking doesn't walk the history.
+    pub fn last_command_status(&self) -> Option<i32> {
+        self.last_command_status
+    }
+
+    // What the window should be called: the title, or failing that the icon
+    // name, which egui has no other use for.
+    pub fn window_title(&self) -> Option<&str> {
+        self.title.as_deref().or(self.icon_name.as_deref())
+    }
+
+    // XTWINOPS 22/23: `which` is 0 for both, 1 for the icon name, 2 for the
+    // title.
+    fn push_title(&mut self, which: usize) {
+        if which != 2 {
+            push_limited(&mut self.icon_name_stack, self.icon_name.clone(), TITLE_STACK_LIMIT);
+        }
+        if which != 1 {
+            push_limited(&mut self.title_stack, self.title.clone(), TITLE_STACK_LIMIT);
+        }
+    }
+
+    fn pop_title(&mut self, which: usize) {
+        if which != 2
+            && let Some(icon_name) = self.icon_name_stack.pop()
+        {
+            self.icon_name = icon_name;
+        }
+        if which != 1
+            && let Some(title) = self.title_stack.pop()
+        {
+            self.title = title;
+        }
+    }
+
+    pub fn alt_screen(&self) -> bool {
+        self.alt_screen
+    }
+
+    pub fn key_modes(&self) -> KeyModes {
+        KeyModes {
+            kitty_flags: self.kitty_flags.last().copied().unwrap_or(0),
+            ..self.key_modes
+        }
+    }
+
+    // CSI = flags ; mode u: replace (1), add (2) or remove (3) flags on the
+    // top of the stack.
+    fn set_kitty_flags(&mut self, flags: u8, mode: usize) {
+        if self.kitty_flags.is_empty() {
+            self.kitty_flags.push(0);
+        }
+        let top = self.kitty_flags.last_mut().unwrap();
+        match mode {
+            1 => *top = flags,
+            2 => *top |= flags,
+            3 => *top &= !flags,
+            _ => {}
+        }
+    }
+
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        // Only the primary screen is reflowed; full-screen apps redraw the
+        // alternate one themselves after SIGWINCH.
+        self.grid.resize(rows, cols, !self.alt_screen);
+        self.inactive.resize(rows, cols, self.alt_screen);
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = (self.grid.cursor, self.template);
+    }
+
+    fn restore_cursor(&mut self) {
+        let (cursor, template) = self.saved_cursor;
+        self.grid.move_to(cursor.row, cursor.col);
+        // the hyperlink is not saved: its slot may have been reused since
+        self.template = Cell {
+            link: self.template.link,
+            ..template
+        };
+    }
+
+    // Swap the primary and alternate screens. The cursor position carries
+    // over, as in xterm.
+    fn swap_screens(&mut self) {
+        let cursor = self.grid.cursor;
+        std::mem::swap(&mut self.grid, &mut self.inactive);
+        self.grid.move_to(cursor.row, cursor.col);
+        self.grid.autowrap = self.inactive.autowrap;
+        std::mem::swap(&mut self.kitty_flags, &mut self.inactive_kitty_flags);
+        std::mem::swap(&mut self.saved_cursor, &mut self.inactive_saved_cursor);
+        self.alt_screen = !self.alt_screen;
+    }
+
+    fn enter_alt_screen(&mut self, mode: u16) {
+        if self.alt_screen {
+            return;
+        }
+        if mode == 1049 {
+            self.save_cursor();
+        }
+        self.swap_screens();
+        if mode == 1049 {
+            self.grid.clear(&Cell::default());
+        }
+    }
+
+    fn leave_alt_screen(&mut self, mode: u16) {
+        if !self.alt_screen {
+            return;
+        }
+        if mode != 47 {
+            self.grid.clear(&Cell::default());
+        }
+        self.swap_screens();
+        if mode == 1049 {
+            self.restore_cursor();
+        }
+    }
+
+    // SGR: update the attributes used for new cells.
+    fn sgr(&mut self, params: &Params) {
+        let t = &mut self.template;
+        let mut iter = params.iter();
+        // a hyperlink is not an attribute and outlives SGR 0
+        let reset = Cell {
+            link: t.link,
+            ..Cell::default()
+        };
+        if params.is_empty() {
+            *t = reset;
+        }
+        while let Some(param) = iter.next() {
+            match param[0] {
+                0 => *t = reset,
+                1 => t.flags.insert(Flags::BOLD),
+                2 => t.flags.insert(Flags::DIM),
+                3 => t.flags.insert(Flags::ITALIC),
+                4 => {
+                    t.underline = match param.get(1) {
+                        Some(0) => Underline::None,
+                        Some(2) => Underline::Double,
+                        Some(3) => Underline::Curly,
+                        Some(4) => Underline::Dotted,
+                        Some(5) => Underline::Dashed,
+                        _ => Underline::Single,
+                    }
+                }
+                5 | 6 => t.flags.insert(Flags::BLINK),
+                7 => t.flags.insert(Flags::INVERSE),
+                8 => t.flags.insert(Flags::HIDDEN),
+                9 => t.flags.insert(Flags::STRIKE),
+                22 => {
+                    t.flags.remove(Flags::BOLD);
+                    t.flags.remove(Flags::DIM);
+                }
+                23 => t.flags.remove(Flags::ITALIC),
+                21 => t.underline = Underline::Double,
+                24 => t.underline = Underline::None,
+                25 => t.flags.remove(Flags::BLINK),
+                27 => t.flags.remove(Flags::INVERSE),
+                28 => t.flags.remove(Flags::HIDDEN),
+                29 => t.flags.remove(Flags::STRIKE),
+                n @ 30..=37 => t.fg = Color::Indexed(n as u8 - 30),
+                38 => t.fg = extended_color(param, &mut iter).unwrap_or(t.fg),
+                39 => t.fg = Color::Default,
+                n @ 40..=47 => t.bg = Color::Indexed(n as u8 - 40),
+                48 => t.bg = extended_color(param, &mut iter).unwrap_or(t.bg),
+                49 => t.bg = Color::Default,
+                58 => {
+                    t.underline_color = extended_color(param, &mut iter).unwrap_or(t.underline_color);
+                }
+                59 => t.underline_color = Color::Default,
+                n @ 90..=97 => t.fg = Color::Indexed(n as u8 - 90 + 8),
+                n @ 100..=107 => t.bg = Color::Indexed(n as u8 - 100 + 8),
+                _ => {}
+            }
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            1 => self.key_modes.app_cursor = on,
+            7 => self.grid.autowrap = on,
+            12 => self.cursor_blink = on,
+            25 => self.cursor_visible = on,
+            47 | 1047 | 1049 if on => self.enter_alt_screen(mode),
+            47 | 1047 | 1049 => self.leave_alt_screen(mode),
+            1048 if on => self.save_cursor(),
+            1048 => self.restore_cursor(),
+            9 | 1000 | 1002 | 1003 if !on => self.mouse_modes.tracking = MouseTracking::Off,
+            9 => self.mouse_modes.tracking = MouseTracking::X10,
+            1000 => self.mouse_modes.tracking = MouseTracking::Normal,
+            1002 => self.mouse_modes.tracking = MouseTracking::ButtonEvent,
+            1003 => self.mouse_modes.tracking = MouseTracking::AnyEvent,
+            1006 | 1015 if !on => self.mouse_modes.encoding = MouseEncoding::Default,
+            1006 => self.mouse_modes.encoding = MouseEncoding::Sgr,
+            1015 => self.mouse_modes.encoding = MouseEncoding::Urxvt,
+            2004 => self.bracketed_paste = on,
+            _ => {}
+        }
+    }
+}
+
+impl Perform for Term {
+    fn print(&mut self, c: char) {
+        self.grid.put_char(c, &self.template);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' | 0x0b | 0x0c => self.grid.linefeed(&self.template),
+            b'\r' => self.grid.carriage_return(),
+            0x08 => self.grid.backspace(),
+            b'\t' => self.grid.tab(),
+            // BEL, SO/SI and friends have no visible effect yet
+            _ => {}
+        }
+    }
+
+    fn hook(&mut self, _params: &Params, _intermediates: &[u8], _ignore: bool, _action: char) {
+        // DCS strings (sixel, DECRQSS, ...) are swallowed until unhook.
+    }
+
+    fn put(&mut self, _byte: u8) {}
+
+    fn unhook(&mut self) {}
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params {
+            [kind @ (b"0" | b"1" | b"2"), text @ ..] if !text.is_empty() => {
+                // the title itself may contain semicolons
+                let text = Some(String::from_utf8_lossy(&text.join(&b';')).into_owned());
+                if *kind != b"2" {
+                    self.icon_name = text.clone();
+                }
+                if *kind != b"1" {
+                    self.title = text;
+                }
+            }
+            [b"8", params, uri @ ..] => self.set_hyperlink(params, uri),
+            // OSC 133 ; A/B/C/D [; exit status] from shell integration;
+            // other options after the kind are ignored
+            [b"133", kind, rest @ ..] => {
+                let kind = match *kind {
+                    b"A" => MarkKind::Prompt,
+                    b"B" => MarkKind::Input,
+                    b"C" => MarkKind::Output,
+                    b"D" => {
+                        let status = rest.first().and_then(|s| std::str::from_utf8(s).ok()?.parse().ok());
+                        self.last_command_status = status;
+                        MarkKind::Finished(status)
+                    }
+                    _ => return,
+                };
+                self.grid.mark(kind);
+            }
+            // OSC 52 ; targets ; base64 data. Every target means our one
+            // clipboard; data that isn't base64 is ignored.
+            [b"52", _, b"?"] => self.events.push(TermEvent::ReadClipboard),
+            [b"52", _, data] if base64_len(data) > self.osc52_max_bytes => {
+                eprintln!("osc52: refused to copy {} encoded bytes", data.len());
+            }
+            [b"52", _, data] => {
+                if let Ok(bytes) = BASE64.decode(data) {
+                    let text = String::from_utf8_lossy(&bytes).into_owned();
+                    self.events.push(TermEvent::SetClipboard(text));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let t = self.template;
+        let g = &mut self.grid;
+        match (intermediates, action) {
+            ([], 'A') => g.move_up(arg(params, 0, 1)),
+            ([], 'B' | 'e') => g.move_down(arg(params, 0, 1)),
+            ([], 'C' | 'a') => g.move_right(arg(params, 0, 1)),
+            ([], 'D') => g.move_left(arg(params, 0, 1)),
+            ([], 'E') => {
+                g.move_down(arg(params, 0, 1));
+                g.carriage_return();
+            }
+            ([], 'F') => {
+                g.move_up(arg(params, 0, 1));
+                g.carriage_return();
+            }
+            ([], 'G' | '`') => g.move_to(g.cursor.row, arg(params, 0, 1) - 1),
+            ([], 'd') => g.move_to(arg(params, 0, 1) - 1, g.cursor.col),
+            ([], 'H' | 'f') => g.move_to(arg(params, 0, 1) - 1, arg(params, 1, 1) - 1),
+            ([], 'J') => match arg(params, 0, 0) {
+                0 => g.clear_below(&t),
+                1 => g.clear_above(&t),
+                2 => g.clear(&t),
+                3 => g.scrollback.clear(),
+                _ => {}
+            },
+            ([], 'K') => match arg(params, 0, 0) {
+                0 => g.clear_line_right(&t),
+                1 => g.clear_line_left(&t),
+                2 => g.clear_line(&t),
+                _ => {}
+            },
+            ([], 'L') => g.insert_lines(arg(params, 0, 1), &t),
+            ([], 'M') => g.delete_lines(arg(params, 0, 1), &t),
+            ([], '@') => g.insert_chars(arg(params, 0, 1), &t),
+            ([], 'P') => g.delete_chars(arg(params, 0, 1), &t),
+            ([], 'X') => g.erase_chars(arg(params, 0, 1), &t),
+            ([], 'S') => g.scroll_up(arg(params, 0, 1), &t),
+            ([], 'T') => g.scroll_down(arg(params, 0, 1), &t),
+            ([], 'r') => {
+                let rows = g.rows();
+                g.set_scroll_region(arg(params, 0, 1) - 1, arg(params, 1, rows) - 1);
+            }
+            ([], 's') => self.save_cursor(),
+            ([], 'u') => self.restore_cursor(),
+            ([b'?'], 'h' | 'l') => {
+                for p in params.iter() {
+                    self.set_private_mode(p[0], action == 'h');
+                }
+            }
+            ([], 'm') => self.sgr(params),
+            // XTMODKEYS: only modifyOtherKeys (resource 4) is supported
+            ([b'>'], 'm') if arg(params, 0, 0) == 4 => {
+                self.key_modes.modify_other_keys = arg(params, 1, 0) as u8;
+            }
+            ([b'>'], 'n') if arg(params, 0, 0) == 4 => self.key_modes.modify_other_keys = 0,
+            // XTWINOPS: only the title stack
+            ([], 't') => match arg(params, 0, 0) {
+                22 => self.push_title(arg(params, 1, 0)),
+                23 => self.pop_title(arg(params, 1, 0)),
+                _ => {}
+            },
+            // kitty keyboard protocol: push, pop, set and query flags
+            ([b'>'], 'u') => {
+                // a runaway app must not grow the stack without bound
+                push_limited(&mut self.kitty_flags, arg(params, 0, 0) as u8 & KITTY_ALL_FLAGS, KITTY_STACK_LIMIT);
+            }
+            ([b'<'], 'u') => {
+                let n = arg(params, 0, 1).min(self.kitty_flags.len());
+                self.kitty_flags.truncate(self.kitty_flags.len() - n);
+            }
+            ([b'='], 'u') => self.set_kitty_flags(arg(params, 0, 0) as u8 & KITTY_ALL_FLAGS, arg(params, 1, 1)),
+            ([b'?'], 'u') => {
+                let flags = self.key_modes().kitty_flags;
+                self.responses.extend_from_slice(format!("\x1b[?{flags}u").as_bytes());
+            }
+            // DA1: a VT220 with ANSI color. Clients probing for the kitty
+            // protocol send it after `CSI ? u` and wait for this reply.
+            ([], 'c') if arg(params, 0, 0) == 0 => self.responses.extend_from_slice(b"\x1b[?62;22c"),
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        let t = self.template;
+        match (intermediates, byte) {
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'D') => self.grid.linefeed(&t),
+            ([], b'E') => {
+                self.grid.carriage_return();
+                self.grid.linefeed(&t);
+            }
+            ([], b'M') => self.grid.reverse_index(&t),
+            // DECKPAM / DECKPNM
+            ([], b'=') => self.key_modes.app_keypad = true,
+            ([], b'>') => self.key_modes.app_keypad = false,
+            ([], b'c') => {
+                let responses = std::mem::take(&mut self.responses);
+                let events = std::mem::take(&mut self.events);
+                *self = Self::new(self.grid.rows(), self.grid.cols(), self.scrollback_lines);
+                self.responses = responses;
+                self.events = events;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_line(term: &Term, row: usize) -> String {
+        let cells = &term.grid.visible_row(0, row).cells;
+        cells.iter().flat_map(Cell::text).collect::<String>().trim_end().to_owned()
+    }
+
+    #[test]
+    fn multibyte_chars_fed_byte_by_byte() {
+        let text = "héllo 世界 🎉 ok";
+        let mut term = Term::new(2, 40, 0);
+        for byte in text.bytes() {
+            term.feed(&[byte]);
+        }
+        assert_eq!(screen_line(&term, 0), text);
+        // wide characters take two cells, as wcwidth says
+        assert_eq!(term.grid.cursor.col, 16);
+    }
+
+    #[test]
+    fn saved_cursor_per_screen() {
+        let mut term = Term::new(8, 10, 0);
+        // a DECSC on the alternate screen leaves the one 1049 saved alone
+        term.feed(b"\x1b[5;6H\x1b[?1049h\x1b[2;2H\x1b7\x1b[?1049l");
+        assert_eq!(term.grid.cursor, Cursor { row: 4, col: 5 });
+
+        // DECSC saves the attributes with the position
+        term.feed(b"\x1b[1;31m\x1b[3;3H\x1b7\x1b[0m\x1b[H\x1b8x");
+        assert_eq!(term.grid.cursor, Cursor { row: 2, col: 3 });
+        let cell = term.grid.visible_row(0, 2).cells[2];
+        assert_eq!((cell.fg, cell.flags.contains(Flags::BOLD)), (Color::Indexed(1), true));
+    }
+
+    #[test]
+    fn multibyte_chars_split_at_every_chunk_boundary() {
+        let text = "aé世🎉b";
+        let bytes = text.as_bytes();
+        for split in 0..=bytes.len() {
+            let mut term = Term::new(2, 20, 0);
+            term.feed(&bytes[..split]);
+            term.feed(&bytes[split..]);
+            assert_eq!(screen_line(&term, 0), text, "split at byte {split}");
+        }
+    }
+
+    #[test]
+    fn escape_sequence_split_across_chunks() {
+        let bytes = "\x1b[31mré\x1b[0m\r\nnext".as_bytes();
+        let mut term = Term::new(3, 20, 0);
+        for chunk in bytes.chunks(3) {
+            term.feed(chunk);
+        }
+        assert_eq!(screen_line(&term, 0), "ré");
+        assert_eq!(screen_line(&term, 1), "next");
+        assert_eq!(term.grid.visible_row(0, 0).cells[1].fg, Color::Indexed(1));
+        assert_eq!(term.grid.visible_row(0, 1).cells[0].fg, Color::Default);
+    }
+
+    #[test]
+    fn kitty_flag_stack_per_screen() {
+        let mut term = Term::new(4, 10, 0);
+        // the documented support check: the flags, then DA1
+        term.feed(b"\x1b[?u\x1b[c");
+        assert_eq!(term.take_responses(), b"\x1b[?0u\x1b[?62;22c");
+        term.feed(b"\x1b[>1u\x1b[>3u\x1b[?u");
+        assert_eq!(term.take_responses(), b"\x1b[?3u");
+        term.feed(b"\x1b[<u\x1b[?u");
+        assert_eq!(term.take_responses(), b"\x1b[?1u");
+        term.feed(b"\x1b[=8;2u");
+        assert_eq!(term.key_modes().kitty_flags, 9);
+
+        // the alternate screen starts from its own, empty stack
+        term.feed(b"\x1b[?1049h");
+        assert_eq!(term.key_modes().kitty_flags, 0);
+        term.feed(b"\x1b[>31u\x1b[?1049l");
+        assert_eq!(term.key_modes().kitty_flags, 9);
+        term.feed(b"\x1b[<5u");
+        assert_eq!(term.key_modes().kitty_flags, 0);
+    }
+
+    #[test]
+    fn osc52_clipboard() {
+        let mut term = Term::new(4, 10, 0);
+        term.feed(b"\x1b]52;c;aGVsbG8=\x07\x1b]52;;?\x1b\\\x1b]52;c;!!\x07");
+        let expected = [TermEvent::SetClipboard("hello".to_owned()), TermEvent::ReadClipboard];
+        assert_eq!(term.take_events(), expected);
+        term.clipboard_contents("hi");
+        assert_eq!(term.take_responses(), b"\x1b]52;c;aGk=\x1b\\");
+
+        // too big to be worth decoding
+        term.osc52_max_bytes = 5;
+        term.feed(b"\x1b]52;c;aGVsbG8=\x07\x1b]52;c;aGVsbG8h\x07");
+        assert_eq!(term.take_events(), [TermEvent::SetClipboard("hello".to_owned())]);
+    }
+
+    #[test]
+    fn title_stack() {
+        let mut term = Term::new(4, 10, 0);
+        term.feed(b"\x1b]0;shell\x07");
+        assert_eq!(term.window_title(), Some("shell"));
+        // what vim does: save, retitle, restore on exit
+        term.feed(b"\x1b[22;0t\x1b]2;vim file.txt\x07");
+        assert_eq!(term.window_title(), Some("vim file.txt"));
+        term.feed(b"\x1b[23;0t");
+        assert_eq!(term.window_title(), Some("shell"));
+        // popping an empty stack changes nothing
+        term.feed(b"\x1b[23;0t");
+        assert_eq!(term.window_title(), Some("shell"));
+
+        term.feed(b"\x1b[22;2t\x1b]1;icon\x07\x1b]2;title\x07\x1b[23;2t");
+        assert_eq!(term.window_title(), Some("shell"));
+        assert_eq!(term.icon_name.as_deref(), Some("icon"));
+
+        term.feed(b"\x1b]2;a; b\x07");
+        assert_eq!(term.window_title(), Some("a; b"));
+    }
+
+    #[test]
+    fn osc8_hyperlinks() {
+        let mut term = Term::new(4, 20, 0);
+        term.feed(b"\x1b]8;;https://example.com/a;b\x1b\\li\x1b[0mnk\x1b]8;;\x1b\\ x");
+        let cells = &term.grid.visible_row(0, 0).cells;
+        let link = cells[0].link;
+        assert_ne!(link, 0);
+        // SGR 0 in the middle of the link text doesn't end it
+        assert!(cells[..4].iter().all(|c| c.link == link));
+        assert_eq!(cells[5].link, 0);
+        let hyperlink = term.hyperlink(link).unwrap();
+        assert_eq!(hyperlink.uri, "https://example.com/a;b");
+        assert_eq!(hyperlink.id, None);
+
+        // the same id makes separate runs one link; anonymous links stay apart
+        term.feed(b"\x1b]8;id=7;file:///x\x07a\x1b]8;;\x07 \x1b]8;id=7;file:///x\x07b\x1b]8;;\x07");
+        term.feed(b"\x1b]8;;file:///x\x07c\x1b]8;;\x07");
+        let cells = &term.grid.visible_row(0, 0).cells;
+        assert_eq!(cells[6].link, cells[8].link);
+        assert_ne!(cells[6].link, cells[9].link);
+        assert_eq!(term.hyperlink(cells[6].link).unwrap().id.as_deref(), Some("7"));
+    }
+
+    fn marks(term: &Term) -> Vec<(usize, MarkKind, usize)> {
+        term.grid.marks().map(|(line, m)| (line, m.kind, m.col)).collect()
+    }
+
+    #[test]
+    fn osc133_prompt_marks() {
+        let mut term = Term::new(4, 10, 100);
+        term.feed(b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07a b\r\n");
+        term.feed(b"\x1b]133;D;1\x07\x1b]133;A;cl=m\x07$ \x1b]133;B\x07");
+        // a prompt redrawn in place doesn't add marks
+        term.feed(b"\r\x1b]133;A\x07$ \x1b]133;B\x07");
+        use MarkKind::*;
+        let session = [
+            (0, Prompt, 0),
+            (0, Input, 2),
+            (1, Output, 0),
+            (2, Finished(Some(1)), 0),
+            (2, Prompt, 0),
+            (2, Input, 2),
+        ];
+        assert_eq!(marks(&term), session);
+        assert_eq!(term.last_command_status(), Some(1));
+        // marks keep their lines as they scroll into history
+        term.feed(b"\r\n\x1b]133;C\x07\r\n\r\n\r\n\x1b]133;D\x07");
+        assert_eq!(term.grid.scrollback.len(), 3);
+        assert_eq!(marks(&term)[..6], session);
+        assert_eq!(marks(&term)[6..], [(3, Output, 0), (6, Finished(None), 0)]);
+        assert_eq!(term.last_command_status(), None);
+    }
+
+    #[test]
+    fn osc133_marks_survive_reflow() {
+        let mut term = Term::new(4, 10, 100);
+        term.feed(b"\x1b]133;A\x07$ \x1b]133;B\x07echo 0123456789\r\n\x1b]133;C\x07");
+        use MarkKind::*;
+        assert_eq!(marks(&term), [(0, Prompt, 0), (0, Input, 2), (2, Output, 0)]);
+        term.resize(4, 20);
+        assert_eq!(marks(&term), [(0, Prompt, 0), (0, Input, 2), (1, Output, 0)]);
+        term.resize(4, 5);
+        assert_eq!(marks(&term), [(0, Prompt, 0), (0, Input, 2), (4, Output, 0)]);
+    }
+
+    #[test]
+    fn unused_osc8_links_are_freed() {
+        let mut term = Term::new(4, 20, 10);
+        term.feed(b"\x1b]8;id=keep;https://a\x07k\x1b]8;;\x07\r\n");
+        for i in 0..1000 {
+            term.feed(format!("\x1b]8;;file:///f{i}\x1b\\f{i}\x1b]8;;\x1b\\\r\n").as_bytes());
+        }
+        // the screen and history hold 14 rows, each with one link
+        assert!(term.links.len() <= 2 * LINKS_SWEEP_MIN, "{} slots", term.links.len());
+        assert!(term.links.iter().flatten().count() < 300);
+        let cells = &term.grid.visible_row(0, 2).cells;
+        assert_eq!(term.hyperlink(cells[0].link).unwrap().uri, "file:///f999");
+        assert!(term.link_ids.is_empty());
+
+        // a link with an id is found again while something still shows it
+        term.feed(b"\x1b[H\x1b]8;id=x;https://b\x07a\x1b]8;;\x07");
+        // each overwrites the last, leaving it unused
+        for _ in 0..1000 {
+            term.feed(b"\x1b[4H\x1b]8;;https://c\x07c\x1b]8;;\x07");
+        }
+        term.feed(b"\x1b]8;id=x;https://b\x07b\x1b]8;;\x07");
+        assert!(term.links.len() <= 2 * LINKS_SWEEP_MIN, "{} slots", term.links.len());
+        let link = term.grid.visible_row(0, 0).cells[0].link;
+        assert_eq!(term.grid.visible_row(0, 3).cells[1].link, link);
+    }
+
+    // The cell "x" gets printed as after `sgr`.
+    fn styled(sgr: &str) -> Cell {
+        let mut term = Term::new(2, 10, 0);
+        term.feed(format!("{sgr}x").as_bytes());
+        term.grid.visible_row(0, 0).cells[0]
+    }
+
+    fn cell(fg: Color, bg: Color, flags: &[Flags]) -> Cell {
+        let mut cell = Cell { c: 'x', fg, bg, ..Cell::default() };
+        for flag in flags {
+            cell.flags.insert(*flag);
+        }
+        cell
+    }
+
+    #[test]
+    fn sgr_colors_and_resets() {
+        use Color::{Default as D, Indexed, Rgb};
+        let table: &[(&str, Cell)] = &[
+            ("\x1b[31;42m", cell(Indexed(1), Indexed(2), &[])),
+            ("\x1b[91;102m", cell(Indexed(9), Indexed(10), &[])),
+            ("\x1b[38;5;208m", cell(Indexed(208), D, &[])),
+            ("\x1b[48;5;17m", cell(D, Indexed(17), &[])),
+            ("\x1b[38;2;1;2;3m", cell(Rgb(1, 2, 3), D, &[])),
+            ("\x1b[38:2:1:2:3m", cell(Rgb(1, 2, 3), D, &[])),
+            // colon form with the (empty) color space id
+            ("\x1b[38:2::1:2:3m", cell(Rgb(1, 2, 3), D, &[])),
+            ("\x1b[48:5:17m", cell(D, Indexed(17), &[])),
+            // what follows a semicolon-form color is still read
+            ("\x1b[38;5;1;1m", cell(Indexed(1), D, &[Flags::BOLD])),
+            ("\x1b[38;2;1;2;3;3m", cell(Rgb(1, 2, 3), D, &[Flags::ITALIC])),
+            ("\x1b[31;39;42;49m", cell(D, D, &[])),
+            ("\x1b[1;2;3;7m", cell(D, D, &[Flags::BOLD, Flags::DIM, Flags::ITALIC, Flags::INVERSE])),
+            ("\x1b[1;2;22m", cell(D, D, &[])),
+            ("\x1b[3;23m", cell(D, D, &[])),
+            ("\x1b[7;27m", cell(D, D, &[])),
+            ("\x1b[5;8;9;25;28;29m", cell(D, D, &[])),
+            ("\x1b[31;1;4;0m", cell(D, D, &[])),
+            ("\x1b[31;1m\x1b[m", cell(D, D, &[])),
+        ];
+        for (sgr, expected) in table {
+            assert_eq!(styled(sgr), *expected, "{sgr:?}");
+        }
+
+        // SGR 0 resets attributes but not the hyperlink
+        let mut term = Term::new(2, 10, 0);
+        term.feed(b"\x1b]8;;https://a\x07\x1b[1;31;0mx");
+        let x = term.grid.visible_row(0, 0).cells[0];
+        assert_ne!(x.link, 0);
+        assert_eq!(Cell { link: 0, ..x }, cell(D, D, &[]));
+    }
+
+    #[test]
+    fn sgr_underline_styles_and_colors() {
+        use Color::{Default as D, Indexed, Rgb};
+        let table: &[(&str, Underline, Color)] = &[
+            ("\x1b[4m", Underline::Single, D),
+            ("\x1b[4:0m", Underline::None, D),
+            ("\x1b[4:1m", Underline::Single, D),
+            ("\x1b[4:2m", Underline::Double, D),
+            ("\x1b[4:3m", Underline::Curly, D),
+            ("\x1b[4:4m", Underline::Dotted, D),
+            ("\x1b[4:5m", Underline::Dashed, D),
+            ("\x1b[21m", Underline::Double, D),
+            ("\x1b[4:3;24m", Underline::None, D),
+            ("\x1b[4;58:5:9m", Underline::Single, Indexed(9)),
+            ("\x1b[58;5;9m", Underline::None, Indexed(9)),
+            ("\x1b[58;2;1;2;3m", Underline::None, Rgb(1, 2, 3)),
+            ("\x1b[58:2::1:2:3m", Underline::None, Rgb(1, 2, 3)),
+            ("\x1b[58:5:9;59m", Underline::None, D),
+            // the underline color is an attribute SGR 0 resets
+            ("\x1b[4:3;58:5:9;0m", Underline::None, D),
+        ];
+        for (sgr, underline, color) in table {
+            let cell = styled(sgr);
+            assert_eq!((cell.underline, cell.underline_color), (*underline, *color), "{sgr:?}");
+            assert_eq!(cell.fg, D, "{sgr:?}");
+        }
+    }
+}
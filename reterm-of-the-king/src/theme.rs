@@ -0,0 +1,210 @@
+use eframe::egui::Color32;
+use serde::Deserialize;
+
+use crate::grid::Color;
+
+// Colors used to turn cell attributes into pixels.
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub ansi: [Color32; 16],
+    pub foreground: Color32,
+    pub background: Color32,
+    pub cursor: Color32,
+    pub selection: Color32,
+}
+
+// A theme as written in the config file. Every color is optional and falls
+// back to the xterm theme; colors are "#rrggbb" strings.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSpec {
+    pub ansi: Option<Vec<String>>,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub cursor: Option<String>,
+    pub selection: Option<String>,
+}
+
+fn hex(s: &str) -> Result<Color32, String> {
+    let digits = s.strip_prefix('#').unwrap_or(s);
+    // from_str_radix alone would take a sign
+    if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("bad color {s:?}, expected #rrggbb"));
+    }
+    let v = u32::from_str_radix(digits, 16).map_err(|_| format!("bad color {s:?}"))?;
+    Ok(Color32::from_rgb((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+fn palette(colors: [u32; 16]) -> [Color32; 16] {
+    colors.map(|v| Color32::from_rgb((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+impl Default for Theme {
+    // xterm's default palette
+    fn default() -> Self {
+        Self {
+            name: "xterm".to_owned(),
+            ansi: palette([
+                0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+                0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+            ]),
+            foreground: Color32::from_rgb(0xe5, 0xe5, 0xe5),
+            background: Color32::from_rgb(0x00, 0x00, 0x00),
+            cursor: Color32::from_rgb(0xe5, 0xe5, 0xe5),
+            selection: Color32::from_rgb(0x44, 0x44, 0x66),
+        }
+    }
+}
+
+impl Theme {
+    pub fn builtin() -> Vec<Theme> {
+        let solarized = palette([
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ]);
+        vec![
+            Theme::default(),
+            Theme {
+                name: "solarized-dark".to_owned(),
+                ansi: solarized,
+                foreground: Color32::from_rgb(0x83, 0x94, 0x96),
+                background: Color32::from_rgb(0x00, 0x2b, 0x36),
+                cursor: Color32::from_rgb(0x93, 0xa1, 0xa1),
+                selection: Color32::from_rgb(0x07, 0x36, 0x42),
+            },
+            Theme {
+                name: "solarized-light".to_owned(),
+                ansi: solarized,
+                foreground: Color32::from_rgb(0x65, 0x7b, 0x83),
+                background: Color32::from_rgb(0xfd, 0xf6, 0xe3),
+                cursor: Color32::from_rgb(0x58, 0x6e, 0x75),
+                selection: Color32::from_rgb(0xee, 0xe8, 0xd5),
+            },
+            Theme {
+                name: "gruvbox-dark".to_owned(),
+                ansi: palette([
+                    0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+                    0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+                ]),
+                foreground: Color32::from_rgb(0xeb, 0xdb, 0xb2),
+                background: Color32::from_rgb(0x28, 0x28, 0x28),
+                cursor: Color32::from_rgb(0xeb, 0xdb, 0xb2),
+                selection: Color32::from_rgb(0x50, 0x49, 0x45),
+            },
+        ]
+    }
+
+    pub fn from_spec(name: &str, spec: &ThemeSpec) -> Result<Theme, String> {
+        let mut theme = Theme {
+            name: name.to_owned(),
+            ..Theme::default()
+        };
+        if let Some(ansi) = &spec.ansi {
+            if ansi.len() != 16 {
+                return Err(format!("theme {name:?}: ansi needs 16 colors, got {}", ansi.len()));
+            }
+            for (slot, s) in theme.ansi.iter_mut().zip(ansi) {
+                *slot = hex(s).map_err(|e| format!("theme {name:?}: {e}"))?;
+            }
+        }
+        let fields = [
+            (&spec.foreground, &mut theme.foreground),
+            (&spec.background, &mut theme.background),
+            (&spec.cursor, &mut theme.cursor),
+            (&spec.selection, &mut theme.selection),
+        ];
+        for (value, slot) in fields {
+            if let Some(s) = value {
+                *slot = hex(s).map_err(|e| format!("theme {name:?}: {e}"))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    // Palette lookup for indexed colors: the 16 theme colors, then the
+    // 6×6×6 color cube and the 24-step gray ramp.
+    pub fn indexed(&self, i: u8) -> Color32 {
+        match i {
+            0..=15 => self.ansi[i as usize],
+            16..=231 => {
+                let i = i - 16;
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                Color32::from_rgb(level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            232..=255 => {
+                let v = 8 + (i - 232) * 10;
+                Color32::from_rgb(v, v, v)
+            }
+        }
+    }
+
+    pub fn resolve(&self, color: Color, default: Color32) -> Color32 {
+        match color {
+            Color::Default => default,
+            Color::Indexed(i) => self.indexed(i),
+            Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        let table: &[(&str, Option<Color32>)] = &[
+            ("#ff8000", Some(Color32::from_rgb(0xff, 0x80, 0x00))),
+            ("0a0B0c", Some(Color32::from_rgb(0x0a, 0x0b, 0x0c))),
+            ("#fff", None),
+            ("#ff80001", None),
+            ("#gg0000", None),
+            ("#+12345", None),
+            ("", None),
+        ];
+        for (s, expected) in table {
+            assert_eq!(hex(s).ok(), *expected, "{s:?}");
+        }
+    }
+
+    fn spec(ansi: Option<usize>, background: Option<&str>) -> ThemeSpec {
+        ThemeSpec {
+            ansi: ansi.map(|n| vec!["#010203".to_owned(); n]),
+            background: background.map(str::to_owned),
+            ..ThemeSpec::default()
+        }
+    }
+
+    #[test]
+    fn themes_from_specs() {
+        // unset colors come from the xterm theme
+        let theme = Theme::from_spec("mine", &spec(None, Some("#101010"))).unwrap();
+        assert_eq!(theme.name, "mine");
+        assert_eq!(theme.background, Color32::from_rgb(0x10, 0x10, 0x10));
+        assert_eq!(theme.foreground, Theme::default().foreground);
+        assert_eq!(theme.ansi, Theme::default().ansi);
+
+        let theme = Theme::from_spec("mine", &spec(Some(16), None)).unwrap();
+        assert_eq!(theme.ansi, [Color32::from_rgb(1, 2, 3); 16]);
+
+        let err = Theme::from_spec("mine", &spec(Some(8), None)).err().unwrap();
+        assert_eq!(err, "theme \"mine\": ansi needs 16 colors, got 8");
+        let err = Theme::from_spec("mine", &spec(None, Some("black"))).err().unwrap();
+        assert!(err.starts_with("theme \"mine\": bad color \"black\""), "{err}");
+    }
+
+    #[test]
+    fn indexed_palette() {
+        let theme = Theme::default();
+        assert_eq!(theme.indexed(9), theme.ansi[9]);
+        assert_eq!(theme.indexed(16), Color32::from_rgb(0, 0, 0));
+        assert_eq!(theme.indexed(196), Color32::from_rgb(255, 0, 0));
+        assert_eq!(theme.indexed(110), Color32::from_rgb(135, 175, 215));
+        assert_eq!(theme.indexed(232), Color32::from_rgb(8, 8, 8));
+        assert_eq!(theme.indexed(255), Color32::from_rgb(238, 238, 238));
+        let fg = Color32::from_rgb(1, 1, 1);
+        assert_eq!(theme.resolve(Color::Default, fg), fg);
+        assert_eq!(theme.resolve(Color::Rgb(4, 5, 6), fg), Color32::from_rgb(4, 5, 6));
+    }
+}